@@ -1,4 +1,3 @@
-use ratsio::nats_client::UriVec;
 use ratsio::{NatsClient, NatsClientOptions, RatsioError};
 use std::env;
 
@@ -22,15 +21,17 @@ async fn main() -> Result<(), RatsioError> {
         return Err(RatsioError::GenericError("Invalid input".into()));
     }
 
-    let mut options = NatsClientOptions::default();
-    //options.username = "user".into();
-    //options.password = "passwd".into();
-    options.auth_token = "dag0HTXl4RGg7dXdaJwbC8".into();
-    options.cluster_uris = vec!["nats://localhost:4222".to_string()].into();
+    let options = NatsClientOptions {
+        //username: "user".into(),
+        //password: "passwd".into(),
+        auth_token: "dag0HTXl4RGg7dXdaJwbC8".into(),
+        cluster_uris: vec!["nats://localhost:4222".to_string()].into(),
+        ..Default::default()
+    };
     let nats_client = NatsClient::new(options).await?;
-    let _ = nats_client
+    nats_client
         .publish(args[1].clone(), args[2].as_bytes())
         .await?;
-    let _ = nats_client.close().await?;
+    nats_client.close().await?;
     Ok(())
 }
@@ -25,19 +25,26 @@ async fn main() -> Result<(), RatsioError> {
 
     let subject = args[1].clone();
     //Create nats client
-    let mut options = NatsClientOptions::default();
-    //options.username = "user".into();
-    //options.password = "passwd".into();
-    //options.auth_token = "dag0HTXl4RGg7dXdaJwbC8".into();
-    options.tls_required = true;
-    options.cluster_uris = vec!["nats://localhost:4222".to_string()].into();
+    let options = NatsClientOptions {
+        //username: "user".into(),
+        //password: "passwd".into(),
+        //auth_token: "dag0HTXl4RGg7dXdaJwbC8".into(),
+        tls_required: true,
+        //tls_root_ca: Some(std::fs::read("ca.pem").expect("Unable to read CA bundle")),
+        //tls_identity: Some(TlsIdentity::from_pem(
+        //    std::fs::read("client-cert.pem").expect("Unable to read client certificate"),
+        //    std::fs::read("client-key.pem").expect("Unable to read client key"),
+        //)),
+        cluster_uris: vec!["nats://localhost:4222".to_string()].into(),
+        ..Default::default()
+    };
     let nats_client = NatsClient::new(options).await?;
 
     //subscribe to nats subject 'foo'
     let (sid, mut subscription) = nats_client.subscribe(subject.clone()).await?;
 
     ctrlc::set_handler(move || {
-        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
         let _ = runtime.block_on(nats_client.un_subscribe(&sid));
     })
     .expect("Error setting Ctrl-C handler");
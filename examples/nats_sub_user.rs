@@ -25,17 +25,19 @@ async fn main() -> Result<(), RatsioError> {
 
     let subject = args[1].clone();
     //Create nats client
-    let mut options = NatsClientOptions::default();
-    options.username = "user".into();
-    options.password = "secret".into();
-    options.cluster_uris = vec!["nats://localhost:4222".to_string()].into();
+    let options = NatsClientOptions {
+        username: "user".into(),
+        password: "secret".into(),
+        cluster_uris: vec!["nats://localhost:4222".to_string()].into(),
+        ..Default::default()
+    };
     let nats_client = NatsClient::new(options).await?;
 
     //subscribe to nats subject 'foo'
     let (sid, mut subscription) = nats_client.subscribe(subject.clone()).await?;
 
     ctrlc::set_handler(move || {
-        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
         let _ = runtime.block_on(nats_client.un_subscribe(&sid));
     })
     .expect("Error setting Ctrl-C handler");
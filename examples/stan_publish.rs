@@ -24,9 +24,9 @@ async fn main() -> Result<(), RatsioError> {
     let client_id = ratsio::nuid::next();
     let opts = StanOptions::with_options("localhost:4222", "test-cluster", &client_id[..]);
     let stan_client = StanClient::from_options(opts).await?;
-    let _ = stan_client
+    stan_client
         .publish(args[1].clone(), args[2].as_bytes())
         .await?;
-    let _ = stan_client.close().await?;
+    stan_client.close().await?;
     Ok(())
 }
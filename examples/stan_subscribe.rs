@@ -37,7 +37,7 @@ async fn main() -> Result<(), RatsioError> {
         .await?;
 
     ctrlc::set_handler(move || {
-        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
         let _ = runtime.block_on(stan_client.un_subscribe(&sid));
     })
     .expect("Error setting Ctrl-C handler");
@@ -22,6 +22,9 @@ pub enum RatsioError {
     // Occurs when the host is not provided, removing the ability for TLS to function correctly for server identify verification
     #[error("NoRouteToHostError: Host is missing, can't verify server identity")]
     NoRouteToHostError,
+    /// The TLS handshake with the server failed, or TLS is required and no TLS backend is available
+    #[error("TlsError: {0}")]
+    TlsError(String),
     /// Cannot parse an URL
     #[error("Request Stream closed before a result was obtained.")]
     RequestStreamClosed,
@@ -33,6 +36,18 @@ pub enum RatsioError {
     /// Cannot parse an IP
     #[error("AddrParseError: {0}")]
     AddrParseError(#[from] ::std::net::AddrParseError),
+    /// Cannot parse a server url
+    #[error("InvalidServerUrl: {0}")]
+    InvalidServerUrl(String),
+    /// Connecting to the server, TCP and TLS, took longer than `NatsClientOptions::connect_timeout`
+    #[error("ConnectTimeout: connecting to {0} took too long")]
+    ConnectTimeout(String),
+    /// The server did not complete the handshake within `NatsClientOptions::handshake_timeout`
+    #[error("HandshakeTimeout: no handshake from {0} in time")]
+    HandshakeTimeout(String),
+    /// The `NatsClientOptions::resolver` failed to resolve the host of a server url
+    #[error("ResolveError: cannot resolve {0} => {1}")]
+    ResolveError(String, #[source] io::Error),
     /// Cannot reconnect to server after retrying once
     #[error("CannotReconnectToServer: cannot reconnect to server")]
     CannotReconnectToServer,
@@ -54,9 +69,80 @@ pub enum RatsioError {
     #[error("SubscriptionReachedMaxMsgs after {0} messages")]
     SubscriptionReachedMaxMsgs(u32),
 
+    /// No reply was received before the request timeout
+    #[error("RequestTimeout: no reply received in time")]
+    RequestTimeout,
+    /// The server did not answer the flush PING in time
+    #[error("FlushTimeout: no PONG received in time")]
+    FlushTimeout,
+    /// The buffered messages were not consumed before the drain timeout
+    #[error("DrainTimeout: pending messages not consumed in time")]
+    DrainTimeout,
+    /// The client is draining and does not accept new subscriptions
+    #[error("ConnectionDraining: the client is draining")]
+    ConnectionDraining,
+    /// The server reported that nobody is subscribed to the request subject
+    #[error("NoResponders: no subscribers for the request subject")]
+    NoResponders,
+    /// Message headers were used but the server does not support them
+    #[error("HeadersNotSupported: the server does not support message headers")]
+    HeadersNotSupported,
+    /// A header key is empty or contains `:`, or a key or value contains CR or LF
+    #[error("InvalidHeader: {0}")]
+    InvalidHeader(String),
+
+    /// A subscription reached its pending limits, messages are dropped or held back
+    #[error("SlowConsumer: pending limits reached on subscription {0}")]
+    SlowConsumer(String),
+
+    /// The server rejected the credentials
+    #[error("AuthorizationViolation: the server rejected the credentials")]
+    AuthorizationViolation,
+    /// The client did not authenticate in time
+    #[error("AuthorizationTimeout: authentication took too long")]
+    AuthorizationTimeout,
+    /// The user is not allowed to publish to the subject
+    #[error("PublishPermissionViolation: not allowed to publish to {0}")]
+    PublishPermissionViolation(String),
+    /// The user is not allowed to subscribe to the subject
+    #[error("SubscribePermissionViolation: not allowed to subscribe to {0}")]
+    SubscribePermissionViolation(String),
+    /// The server did not get the client PONGs in time and closed the connection
+    #[error("StaleConnection: the server closed the connection")]
+    StaleConnection,
+    /// The server refused the connection as it reached its connection limit
+    #[error("MaxConnectionsExceeded: the server refused the connection")]
+    MaxConnectionsExceeded,
+    /// The server closed the connection because the client did not read fast enough
+    #[error("ServerSlowConsumer: the server closed the connection")]
+    ServerSlowConsumer,
+    /// The server could not parse what the client sent
+    #[error("ParserError: {0}")]
+    ParserError(String),
+    /// Any other -ERR sent by the server
+    #[error("ServerError: {0}")]
+    ServerError(String),
+
+    /// Publishing while reconnecting and the reconnect buffer has no room left
+    #[error("ReconnectBufferFull: too many bytes published while reconnecting")]
+    ReconnectBufferFull,
+    /// The client was closed
+    #[error("ConnectionClosed: the client is closed")]
+    ConnectionClosed,
+
     #[error("Stream Closed for {0}")]
     StreamClosed(String),
 
+    /// The nonce signing callback failed
+    #[error("NonceSigningError: {0}")]
+    NonceSigningError(String),
+    /// The NKey seed could not be decoded
+    #[error("NKeyError: {0}")]
+    NKeyError(String),
+    /// The credentials file could not be read or is malformed
+    #[error("CredentialsError: {0}")]
+    CredentialsError(String),
+
     #[error("Missing ack_inbox for acknowledgement")]
     AckInboxMissing,
 
@@ -64,6 +150,121 @@ pub enum RatsioError {
     SpawnError(#[from] SpawnError),
 }
 
+impl RatsioError {
+    // Classify a -ERR message sent by the server
+    pub(crate) fn from_server_error(msg: &str, max_payload: usize) -> RatsioError {
+        let lowercase = msg.to_lowercase();
+        let subject = |prefix: &str| {
+            let rest = msg[prefix.len()..].trim();
+            match rest.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+                None => rest
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+        };
+        const PUBLISH_VIOLATION: &str = "permissions violation for publish to";
+        const SUBSCRIBE_VIOLATION: &str = "permissions violation for subscription to";
+        if lowercase.starts_with("authorization violation") {
+            RatsioError::AuthorizationViolation
+        } else if lowercase.starts_with("authorization timeout")
+            || lowercase.starts_with("authentication timeout")
+        {
+            RatsioError::AuthorizationTimeout
+        } else if lowercase.starts_with(PUBLISH_VIOLATION) {
+            RatsioError::PublishPermissionViolation(subject(PUBLISH_VIOLATION))
+        } else if lowercase.starts_with(SUBSCRIBE_VIOLATION) {
+            RatsioError::SubscribePermissionViolation(subject(SUBSCRIBE_VIOLATION))
+        } else if lowercase.starts_with("stale connection") {
+            RatsioError::StaleConnection
+        } else if lowercase.starts_with("maximum connections exceeded") {
+            RatsioError::MaxConnectionsExceeded
+        } else if lowercase.starts_with("slow consumer") {
+            RatsioError::ServerSlowConsumer
+        } else if lowercase.starts_with("maximum payload") {
+            RatsioError::MaxPayloadOverflow(max_payload)
+        } else if lowercase.starts_with("parser error")
+            || lowercase.starts_with("unknown protocol operation")
+        {
+            RatsioError::ParserError(msg.to_string())
+        } else {
+            RatsioError::ServerError(msg.to_string())
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl From<native_tls::Error> for RatsioError {
+    fn from(err: native_tls::Error) -> Self {
+        RatsioError::TlsError(err.to_string())
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl From<tokio_rustls::rustls::Error> for RatsioError {
+    fn from(err: tokio_rustls::rustls::Error) -> Self {
+        RatsioError::TlsError(err.to_string())
+    }
+}
+
+#[test]
+fn classify_server_errors() {
+    let classify = |msg| RatsioError::from_server_error(msg, 1024);
+    assert!(matches!(
+        classify("Authorization Violation"),
+        RatsioError::AuthorizationViolation
+    ));
+    assert!(matches!(
+        classify("Authentication Timeout"),
+        RatsioError::AuthorizationTimeout
+    ));
+    assert!(matches!(
+        classify("Stale Connection"),
+        RatsioError::StaleConnection
+    ));
+    assert!(matches!(
+        classify("Maximum Connections Exceeded"),
+        RatsioError::MaxConnectionsExceeded
+    ));
+    assert!(matches!(
+        classify("Slow Consumer"),
+        RatsioError::ServerSlowConsumer
+    ));
+    assert!(matches!(
+        classify("Maximum Payload Violation"),
+        RatsioError::MaxPayloadOverflow(1024)
+    ));
+    assert!(matches!(
+        classify("Unknown Protocol Operation"),
+        RatsioError::ParserError(_)
+    ));
+    assert!(matches!(
+        classify("Something New"),
+        RatsioError::ServerError(msg) if msg == "Something New"
+    ));
+}
+
+#[test]
+fn classify_permission_violations() {
+    match RatsioError::from_server_error("Permissions Violation for Publish to \"foo.bar\"", 0) {
+        RatsioError::PublishPermissionViolation(subject) => assert_eq!(subject, "foo.bar"),
+        _ => unreachable!(),
+    }
+    match RatsioError::from_server_error(
+        "Permissions Violation for Subscription to \"foo.*\" using queue \"q\"",
+        0,
+    ) {
+        RatsioError::SubscribePermissionViolation(subject) => assert_eq!(subject, "foo.*"),
+        _ => unreachable!(),
+    }
+    match RatsioError::from_server_error("Permissions Violation for Subscription to foo.>", 0) {
+        RatsioError::SubscribePermissionViolation(subject) => assert_eq!(subject, "foo.>"),
+        _ => unreachable!(),
+    }
+}
+
 // impl From<RatsioError> for () {
 //     fn from(err: RatsioError) -> Self {
 //          error!(target:"ratsio", "Rats-io error => {}", err);
@@ -20,5 +20,11 @@ pub mod parser;
 pub mod stan_client;
 
 pub use error::RatsioError;
-pub use nats_client::{NatsClient, NatsClientOptions, NatsMessage, NatsSid};
-pub use stan_client::{StanClient, StanMessage, StanOptions, StanSid, StartPosition};
+pub use nats_client::{
+    ClientEvent, DnsResolver, HeaderMap, NatsClient, NatsClientOptions, NatsClientState,
+    NatsMessage, NatsSid, PendingCounts, PendingLimits, PooledServer, ReconnectDelay,
+    ReconnectPolicy, RequestManyOptions, Resolver, ServerUrl, SlowConsumerPolicy, Subscription,
+};
+pub use stan_client::{
+    StanClient, StanMessage, StanOptions, StanSid, StanSubscription, StartPosition,
+};
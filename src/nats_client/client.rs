@@ -1,45 +1,69 @@
 use crate::nats_client::{
-    DisconnectHandler, NatsClient, NatsClientInner, NatsClientOptions, NatsClientState, NatsSid,
+    ClientEvent, DisconnectHandler, ErrorHandler, NatsClient, NatsClientInner, NatsClientOptions,
+    NatsClientState, NatsSid, PendingCounts, PendingLimits, PooledServer, RequestManyOptions,
+    ServerPool, Subscription, TlsIdentity, EVENTS_CAPACITY,
 };
-use crate::net::nats_tcp_stream::NatsTcpStream;
-use crate::ops::{Message, Publish, Subscribe};
+use crate::ops::{HeaderMap, Message, Publish, Subscribe};
 use futures::StreamExt;
 
 use crate::error::RatsioError;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{Notify, RwLock};
 
 use futures::lock::Mutex;
 use futures::stream::Stream;
 use nom::lib::std::collections::HashMap;
+use std::collections::VecDeque;
+
+// Time allowed for the PONG when measuring the round trip time
+const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
 
 impl NatsClient {
+    /// Connects to the first server of `cluster_uris` completing the handshake. Fails with the
+    /// server error, e.g. `AuthorizationViolation`, when the credentials are refused.
     pub async fn new<O>(options: O) -> Result<Arc<Self>, RatsioError>
     where
         O: Into<NatsClientOptions>,
     {
         let opts = options.into();
-        let tcp_stream =
-            NatsClientInner::try_connect(opts.clone(), &opts.cluster_uris.0, false).await?;
-        let (sink, stream) = NatsTcpStream::new(tcp_stream).await.split();
+        if let Some(identity) = opts.tls_identity.as_ref() {
+            identity.check_supported()?;
+        }
+        let cluster_urls = opts.cluster_uris.server_urls()?;
+        let (tcp_stream, server_info, server) =
+            NatsClientInner::try_connect(opts.clone(), &cluster_urls).await?;
+        let (sink, stream) = tcp_stream.split();
+        let mut server_pool = ServerPool::new(&cluster_urls);
+        server_pool.update(&server_info.connect_urls);
 
         let version = 1;
         let client = NatsClient {
             inner: Arc::new(NatsClientInner {
                 conn_sink: Arc::new(Mutex::new(sink)),
-                opts,
-                server_info: RwLock::new(None),
+                opts: opts.clone(),
+                server_info: RwLock::new(Some(server_info)),
+                server_pool: RwLock::new(server_pool),
+                tls_identity: RwLock::new(opts.tls_identity.clone()),
                 subscriptions: Arc::new(Mutex::new(HashMap::default())),
+                request_mux: Mutex::new(None),
+                reconnect_buffer: Mutex::new(Default::default()),
+                pongs: Mutex::new(VecDeque::new()),
                 on_reconnect: tokio::sync::Mutex::new(None),
                 state: RwLock::new(NatsClientState::Connecting),
+                connected_server: RwLock::new(Some(server.clone())),
+                events: broadcast::channel(EVENTS_CAPACITY).0,
+                disconnected: Notify::new(),
                 last_ping: RwLock::new(NatsClientInner::time_in_millis()),
                 reconnect_version: RwLock::new(version),
                 client_ref: RwLock::new(None),
             }),
             disconnect_handlers: RwLock::new(Vec::new()),
+            error_handlers: RwLock::new(Vec::new()),
         };
         match NatsClientInner::start(client.inner.clone(), version, stream).await {
-            Ok(_) => {}
+            Ok(_) => *client.inner.state.write().await = NatsClientState::Connected,
             Err(err) => {
                 let _ = client.close().await;
                 return Err(err);
@@ -63,15 +87,17 @@ impl NatsClient {
         //heartbeat monitor
         let heartbeat_client = arc_client.clone();
         tokio::spawn(async move {
-            let _ = heartbeat_client.inner.monitor_heartbeat().await;
+            let _ = heartbeat_client.inner.monitor_heartbeat(version).await;
         });
+        let reconnect_inner = arc_client.inner.clone();
+        tokio::spawn(async move { reconnect_inner.reconnect_on_disconnect().await });
+        arc_client
+            .inner
+            .emit(ClientEvent::Connected(server.to_string()));
         Ok(arc_client)
     }
 
-    pub async fn subscribe<T>(
-        &self,
-        subject: T,
-    ) -> Result<(NatsSid, impl Stream<Item = Message> + Send + Sync), RatsioError>
+    pub async fn subscribe<T>(&self, subject: T) -> Result<(NatsSid, Subscription), RatsioError>
     where
         T: ToString,
     {
@@ -80,14 +106,14 @@ impl NatsClient {
             ..Default::default()
         };
         debug!("[Nats] - cmd = {:?}", cmd);
-        self.inner.subscribe(cmd).await
+        self.subscribe_inner(cmd).await
     }
 
     pub async fn subscribe_with_group<T>(
         &self,
         subject: T,
         group: T,
-    ) -> Result<(NatsSid, impl Stream<Item = Message> + Send + Sync), RatsioError>
+    ) -> Result<(NatsSid, Subscription), RatsioError>
     where
         T: ToString,
     {
@@ -96,13 +122,127 @@ impl NatsClient {
             queue_group: Some(group.to_string()),
             ..Default::default()
         };
-        self.inner.subscribe(cmd).await
+        self.subscribe_inner(cmd).await
+    }
+
+    /// Subscribe, ending the stream after `max_msgs` messages.
+    pub async fn subscribe_with_limit<T>(
+        &self,
+        subject: T,
+        max_msgs: u32,
+    ) -> Result<(NatsSid, Subscription), RatsioError>
+    where
+        T: ToString,
+    {
+        let cmd = Subscribe {
+            subject: subject.to_string(),
+            ..Default::default()
+        };
+        let (sid, subscription) = self.subscribe_inner(cmd).await?;
+        self.inner.auto_unsubscribe(sid.clone(), max_msgs).await?;
+        Ok((sid, subscription))
+    }
+
+    async fn subscribe_inner(
+        &self,
+        cmd: Subscribe,
+    ) -> Result<(NatsSid, Subscription), RatsioError> {
+        let subject = cmd.subject.clone();
+        let (sid, receiver) = self.inner.subscribe(cmd).await?;
+        let subscription = Subscription::new(sid.clone(), subject, receiver, self.inner.clone());
+        Ok((sid, subscription))
     }
 
     pub async fn un_subscribe(&self, sid: &NatsSid) -> Result<(), RatsioError> {
         self.inner.un_subscribe(sid.clone()).await
     }
 
+    /// Stops receiving messages for the subscription and waits, up to the timeout,
+    /// until the messages already received are consumed. The stream then ends.
+    pub async fn drain_subscription(
+        &self,
+        sid: &NatsSid,
+        timeout: Duration,
+    ) -> Result<(), RatsioError> {
+        self.inner.drain_subscription(sid.clone(), timeout).await
+    }
+
+    /// Drains all subscriptions, flushes the publishes and closes the client.
+    /// The client is in the `Draining` state meanwhile, refusing new subscriptions.
+    pub async fn drain(&self, timeout: Duration) -> Result<(), RatsioError> {
+        self.inner.drain(timeout).await
+    }
+
+    pub async fn state(&self) -> NatsClientState {
+        self.inner.state.read().await.clone()
+    }
+
+    /// Stream of the connection lifecycle events happening from now on,
+    /// starting with `ClientEvent::Connected` if the client is connected.
+    /// The stream ends after `ClientEvent::Closed`.
+    pub async fn events(&self) -> impl Stream<Item = ClientEvent> + Send + Unpin {
+        let receiver = self.inner.events.subscribe();
+        let current = match *self.inner.state.read().await {
+            NatsClientState::Connected => self
+                .inner
+                .connected_server
+                .read()
+                .await
+                .as_ref()
+                .map(|server| ClientEvent::Connected(server.to_string())),
+            NatsClientState::Shutdown => Some(ClientEvent::Closed),
+            _ => None,
+        };
+        let receiver = match current {
+            Some(ClientEvent::Closed) => None,
+            _ => Some(receiver),
+        };
+        let events = futures::stream::unfold(receiver, |receiver| async move {
+            let mut receiver = receiver?;
+            loop {
+                match receiver.recv().await {
+                    Ok(ClientEvent::Closed) => return Some((ClientEvent::Closed, None)),
+                    Ok(event) => return Some((event, Some(receiver))),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(
+                            "{} client events were missed by a slow events stream",
+                            missed
+                        )
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        futures::stream::iter(current).chain(events).boxed()
+    }
+
+    /// Servers used when reconnecting: the configured `cluster_uris`
+    /// followed by the servers advertised by the cluster.
+    pub async fn server_pool(&self) -> Vec<PooledServer> {
+        self.inner.server_pool.read().await.servers()
+    }
+
+    /// Replaces the limits of the messages buffered for the subscription.
+    pub async fn set_pending_limits(
+        &self,
+        sid: &NatsSid,
+        limits: PendingLimits,
+    ) -> Result<(), RatsioError> {
+        self.inner.set_pending_limits(sid, limits).await
+    }
+
+    /// Messages received for the subscription and not yet consumed from its stream.
+    pub async fn pending_counts(&self, sid: &NatsSid) -> Result<PendingCounts, RatsioError> {
+        self.inner.pending_counts(sid).await
+    }
+
+    /// Unsubscribe after `max_msgs` messages in total have been received on the subscription,
+    /// the stream ends after the last one. Fails with `RatsioError::SubscriptionReachedMaxMsgs`
+    /// and unsubscribes at once if they were already received.
+    pub async fn auto_unsubscribe(&self, sid: &NatsSid, max_msgs: u32) -> Result<(), RatsioError> {
+        self.inner.auto_unsubscribe(sid.clone(), max_msgs).await
+    }
+
     pub async fn publish<T>(&self, subject: T, data: &[u8]) -> Result<(), RatsioError>
     where
         T: ToString,
@@ -111,6 +251,26 @@ impl NatsClient {
             subject: subject.to_string(),
             reply_to: None,
             payload: Vec::from(data),
+            headers: None,
+        };
+        self.inner.publish(cmd).await
+    }
+
+    /// Publish with message headers, requires a NATS 2.2+ server.
+    pub async fn publish_with_headers<T>(
+        &self,
+        subject: T,
+        headers: HeaderMap,
+        data: &[u8],
+    ) -> Result<(), RatsioError>
+    where
+        T: ToString,
+    {
+        let cmd = Publish {
+            subject: subject.to_string(),
+            reply_to: None,
+            payload: Vec::from(data),
+            headers: Some(headers),
         };
         self.inner.publish(cmd).await
     }
@@ -128,6 +288,7 @@ impl NatsClient {
             subject: subject.to_string(),
             reply_to: Some(reply_to.to_string()),
             payload: Vec::from(data),
+            headers: None,
         };
         self.inner.publish(cmd).await
     }
@@ -140,14 +301,117 @@ impl NatsClient {
             subject: subject.to_string(),
             payload: Vec::from(data),
             reply_to: None,
+            headers: None,
+        };
+        NatsClientInner::request(&self.inner, cmd, None).await
+    }
+
+    /// Same as `request`, failing with `RatsioError::RequestTimeout` if no reply arrives in time.
+    /// The reply inbox is always unsubscribed, even if the returned future is dropped.
+    pub async fn request_timeout<T>(
+        &self,
+        subject: T,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<Message, RatsioError>
+    where
+        T: ToString,
+    {
+        let cmd = Publish {
+            subject: subject.to_string(),
+            payload: Vec::from(data),
+            reply_to: None,
+            headers: None,
         };
-        self.inner.request(cmd).await
+        NatsClientInner::request(&self.inner, cmd, Some(timeout)).await
+    }
+
+    /// Publishes a request and returns the stream of all the replies, ending after 5 seconds.
+    pub async fn request_many<T>(
+        &self,
+        subject: T,
+        data: &[u8],
+    ) -> Result<impl Stream<Item = Message> + Send, RatsioError>
+    where
+        T: ToString,
+    {
+        self.request_many_with_options(subject, data, RequestManyOptions::default())
+            .await
+    }
+
+    /// Same as `request_many`, with the reply stream ending as set in the options.
+    /// The reply inbox is released once the stream ends or is dropped.
+    pub async fn request_many_with_options<T>(
+        &self,
+        subject: T,
+        data: &[u8],
+        options: RequestManyOptions,
+    ) -> Result<impl Stream<Item = Message> + Send, RatsioError>
+    where
+        T: ToString,
+    {
+        let cmd = Publish {
+            subject: subject.to_string(),
+            payload: Vec::from(data),
+            reply_to: None,
+            headers: None,
+        };
+        NatsClientInner::request_many(&self.inner, cmd, options).await
+    }
+
+    /// Request with message headers, requires a NATS 2.2+ server.
+    pub async fn request_with_headers<T>(
+        &self,
+        subject: T,
+        headers: HeaderMap,
+        data: &[u8],
+    ) -> Result<Message, RatsioError>
+    where
+        T: ToString,
+    {
+        let cmd = Publish {
+            subject: subject.to_string(),
+            payload: Vec::from(data),
+            reply_to: None,
+            headers: Some(headers),
+        };
+        NatsClientInner::request(&self.inner, cmd, None).await
+    }
+
+    /// Returns a unique subject under the configured `inbox_prefix`, suitable as a reply subject.
+    pub fn new_inbox(&self) -> String {
+        self.inner.new_inbox()
+    }
+
+    /// Sends a PING and waits for the server PONG, meaning that everything
+    /// sent before was processed by the server.
+    pub async fn flush(&self, timeout: Duration) -> Result<(), RatsioError> {
+        self.inner.flush(timeout).await
+    }
+
+    /// Measures the round trip time to the server.
+    pub async fn rtt(&self) -> Result<Duration, RatsioError> {
+        let start = Instant::now();
+        self.inner.flush(DEFAULT_FLUSH_TIMEOUT).await?;
+        Ok(start.elapsed())
+    }
+
+    /// Replaces the client certificate presented to the server.
+    /// The current connection is kept, the new identity is used from the next reconnect.
+    pub async fn set_tls_identity(&self, identity: Option<TlsIdentity>) -> Result<(), RatsioError> {
+        if let Some(identity) = identity.as_ref() {
+            identity.check_supported()?;
+        }
+        *self.inner.tls_identity.write().await = identity;
+        Ok(())
     }
 
     pub async fn close(&self) -> Result<(), RatsioError> {
         self.inner.stop().await
     }
 
+    /// Adds a handler called when the connection is lost and again once reconnected,
+    /// `events` tells these apart.
     pub async fn add_disconnect_handler(
         &self,
         handler: DisconnectHandler,
@@ -158,6 +422,22 @@ impl NatsClient {
         Ok(())
     }
 
+    /// Adds a handler called with the errors happening outside of a call to the client,
+    /// such as `RatsioError::SlowConsumer` or the errors sent by the server.
+    pub async fn add_error_handler(&self, handler: ErrorHandler) -> Result<(), RatsioError> {
+        let mut handlers = self.error_handlers.write().await;
+        handlers.push(handler);
+
+        Ok(())
+    }
+
+    pub(in crate::nats_client) async fn on_error(&self, err: &RatsioError) {
+        let handlers = self.error_handlers.read().await;
+        for handler in handlers.iter() {
+            handler(self, err)
+        }
+    }
+
     pub(in crate::nats_client) async fn on_disconnect(&self) {
         let handlers = self.disconnect_handlers.read().await;
         let handlers: &Vec<DisconnectHandler> = handlers.as_ref();
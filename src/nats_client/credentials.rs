@@ -0,0 +1,124 @@
+use crate::error::RatsioError;
+use crate::nats_client::{NatsClientOptions, UserJWT, UserNKey};
+use regex::Regex;
+use std::path::Path;
+
+lazy_static! {
+    // A block is a line of dashes with a title (BEGIN NATS USER JWT, BEGIN USER NKEY SEED, ...),
+    // the value, and a closing line of dashes.
+    static ref CREDS_BLOCK: Regex =
+        Regex::new(r"(?m)^\s*-{3,}[^\n]*-{3,}\s*\n\s*(\S+)\s*\n\s*-{3,}[^\n]*-{3,}\s*$").unwrap();
+}
+
+/// Extracts the user JWT and the nkey seed from a chained credentials file
+pub(crate) fn parse_credentials(contents: &str) -> Result<(String, String), RatsioError> {
+    let mut blocks = CREDS_BLOCK
+        .captures_iter(contents)
+        .filter_map(|captures| captures.get(1))
+        .map(|value| value.as_str().to_string());
+    let jwt = blocks
+        .next()
+        .ok_or_else(|| RatsioError::CredentialsError("missing user JWT".into()))?;
+    let seed = blocks
+        .next()
+        .ok_or_else(|| RatsioError::CredentialsError("missing nkey seed".into()))?;
+    Ok((jwt, seed))
+}
+
+impl NatsClientOptions {
+    /// Authenticate with a credentials file as produced by `nsc` or the `nats` CLI,
+    /// containing the user JWT and the nkey seed used to sign the server nonce.
+    pub fn with_credentials_file<P>(self, path: P) -> Result<Self, RatsioError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            RatsioError::CredentialsError(format!("{} - {}", path.display(), err))
+        })?;
+        self.with_credentials(&contents)
+    }
+
+    /// Same as `with_credentials_file`, from the contents of a credentials file.
+    pub fn with_credentials(self, contents: &str) -> Result<Self, RatsioError> {
+        let (jwt, seed) = parse_credentials(contents)?;
+        let user_nkey = UserNKey::from_seed(&seed)
+            .map_err(|err| RatsioError::CredentialsError(err.to_string()))?;
+        Ok(NatsClientOptions {
+            user_jwt: Some(UserJWT::new(jwt, user_nkey.signer)),
+            ..self
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREDS: &str = r#"-----BEGIN NATS USER JWT-----
+eyJ0eXAiOiJqd3QiLCJhbGciOiJlZDI1NTE5In0.eyJqdGkiOiJURVNUIn0.c2ln
+------END NATS USER JWT------
+
+************************* IMPORTANT *************************
+NKEY Seed printed below can be used to sign and prove identity.
+NKEYs are sensitive and should be treated as secrets.
+
+-----BEGIN USER NKEY SEED-----
+SUAIBDPBAUTWCWBKIO6XHQNINK5FWJW4OHLXC3HQ2KFE4PEJUA44CNHTC4
+------END USER NKEY SEED------
+
+*************************************************************
+"#;
+
+    #[test]
+    fn parse_creds() {
+        let (jwt, seed) = parse_credentials(CREDS).unwrap();
+        assert_eq!(
+            jwt,
+            "eyJ0eXAiOiJqd3QiLCJhbGciOiJlZDI1NTE5In0.eyJqdGkiOiJURVNUIn0.c2ln"
+        );
+        assert_eq!(
+            seed,
+            "SUAIBDPBAUTWCWBKIO6XHQNINK5FWJW4OHLXC3HQ2KFE4PEJUA44CNHTC4"
+        );
+    }
+
+    #[test]
+    fn parse_creds_missing_seed() {
+        let contents = CREDS.split("*****").next().unwrap();
+        match parse_credentials(contents) {
+            Err(RatsioError::CredentialsError(msg)) => assert_eq!(msg, "missing nkey seed"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn with_credentials() {
+        let seed = nkeys::KeyPair::new_user().seed().unwrap();
+        let creds = CREDS.replace(
+            "SUAIBDPBAUTWCWBKIO6XHQNINK5FWJW4OHLXC3HQ2KFE4PEJUA44CNHTC4",
+            &seed,
+        );
+        let opts = NatsClientOptions::default()
+            .with_credentials(&creds)
+            .unwrap();
+        let user_jwt = opts.user_jwt.unwrap();
+        assert_eq!(
+            user_jwt.jwt(),
+            "eyJ0eXAiOiJqd3QiLCJhbGciOiJlZDI1NTE5In0.eyJqdGkiOiJURVNUIn0.c2ln"
+        );
+        assert!(user_jwt.sign("nonce").is_ok());
+    }
+
+    #[test]
+    fn with_credentials_invalid_seed() {
+        let creds = CREDS.replace(
+            "SUAIBDPBAUTWCWBKIO6XHQNINK5FWJW4OHLXC3HQ2KFE4PEJUA44CNHTC4",
+            "SUNOTASEED",
+        );
+        match NatsClientOptions::default().with_credentials(&creds) {
+            Err(RatsioError::CredentialsError(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -1,19 +1,50 @@
 pub mod client;
 mod client_inner;
 mod converters;
+#[cfg(feature = "nkey")]
+mod credentials;
+mod pending;
+mod server_pool;
+mod server_url;
+mod subscription;
 
+pub use crate::net::nats_tcp_stream::TlsIdentity;
+pub use crate::net::resolver::{DnsResolver, Resolver, SystemResolver};
+pub use pending::{PendingCounts, PendingLimits, SlowConsumerPolicy};
+pub use server_pool::PooledServer;
+pub use server_url::ServerUrl;
+pub use subscription::Subscription;
+
+use crate::error::RatsioError;
 use crate::net::nats_tcp_stream::NatsTcpStream;
-use crate::ops::{Message, Op, ServerInfo, Subscribe};
+use crate::ops::{Message, Op, Publish, ServerInfo, Subscribe};
+use pending::PendingQueue;
+use rand::Rng;
+use server_pool::ServerPool;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 use futures::lock::Mutex;
 use futures::stream::SplitSink;
 use nom::lib::std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt::Debug;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tokio::sync::{broadcast, Notify};
+
+// Prefix of the reply subjects, unless overridden with `NatsClientOptions::inbox_prefix`
+pub(crate) const DEFAULT_INBOX_PREFIX: &str = "_INBOX";
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Value of the deprecated `reconnect_timeout` when not set
+const RECONNECT_TIMEOUT_UNSET: u64 = u64::MAX;
+// Same as the other NATS clients
+const DEFAULT_RECONNECT_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+// Events kept for the `NatsClient::events` streams lagging behind
+const EVENTS_CAPACITY: usize = 256;
 
 #[derive(Debug, Clone)]
 pub struct NatsSid(pub(crate) String);
@@ -25,10 +56,16 @@ pub struct NatsClientOptions {
     pub username: String,
     /// Cluster password, can be overwritten by host url nats://<username>:<password>@<host>:<port>
     pub password: String,
-    /// Cluster auth_token
+    /// Cluster auth_token, can be overwritten by host url nats://<token>@<host>:<port>
     pub auth_token: String,
-    /// Whether TLS is required.
+    /// Whether TLS is required. The connection is also upgraded when the server INFO
+    /// requires it, or for the servers with a tls:// url.
     pub tls_required: bool,
+    /// PEM encoded root CA bundle used to verify the server, in addition to the system roots
+    pub tls_root_ca: Option<Vec<u8>>,
+    /// Client certificate for servers running with `tls_verify`,
+    /// can be replaced at runtime with `NatsClient::set_tls_identity`
+    pub tls_identity: Option<TlsIdentity>,
     /// verbosity, default true
     pub verbose: bool,
     /// pedantic, default false
@@ -38,8 +75,16 @@ pub struct NatsClientOptions {
     /// Optional client name
     pub name: String,
 
-    /// Cluster URI in the IP:PORT format
+    /// Cluster URIs, see `ServerUrl` for the format
     pub cluster_uris: UriVec,
+    /// Resolves the server hosts on each connect attempt, the system resolver by default
+    pub resolver: DnsResolver,
+    /// Time allowed to open the connection to a server, the TCP connect and
+    /// the TLS upgrade together, default 2 seconds
+    pub connect_timeout: Duration,
+    /// Time allowed from the TCP connect to the server to send its INFO, then to confirm
+    /// the CONNECT with a PONG, TLS upgrade included, default 5 seconds
+    pub handshake_timeout: Duration,
 
     /// Ping interfval in seconds
     pub ping_interval: u16,
@@ -50,33 +95,63 @@ pub struct NatsClientOptions {
     pub subscribe_on_reconnect: bool,
     /// If connect fails, keep trying, forever,
     pub ensure_connect: bool,
-    /// Time between connection retries
+    /// Delays and limits of the reconnect attempts after the connection is lost
+    pub reconnect_policy: ReconnectPolicy,
+    /// Time between connection retries in milliseconds, unset (`u64::MAX`) by default.
+    /// When set, it replaces the backoff of `reconnect_policy` with this fixed delay.
+    #[deprecated(note = "use `reconnect_policy` instead")]
     pub reconnect_timeout: u64,
+    /// Payload bytes published while reconnecting that are kept and sent once reconnected,
+    /// publishing more fails with `RatsioError::ReconnectBufferFull`. Default 8MB
+    pub reconnect_buffer_size: usize,
     /// When using NATS 2.x decentralized security, supply a user JWT for authN/authZ
     pub user_jwt: Option<UserJWT>,
-    /// Nkey authentication
+    /// User seed (`SU...`) for Nkey authentication, needs the `nkey` feature
+    #[deprecated(note = "use `user_nkey` instead")]
     pub nkey: Option<String>,
+    /// Nkey authentication, from a user seed (`nkey` feature) or a public key and signing callback
+    pub user_nkey: Option<UserNKey>,
+    /// Use a dedicated inbox subscription for every request instead of
+    /// the shared `_INBOX.<nuid>.*` subscription, default false
+    pub old_request_style: bool,
+    /// Prefix of the reply subjects created by the client, default `_INBOX`
+    pub inbox_prefix: String,
+    /// Limits of the messages buffered for each subscription,
+    /// can be changed per subscription with `NatsClient::set_pending_limits`
+    pub pending_limits: PendingLimits,
 }
 
 impl Default for NatsClientOptions {
+    #[allow(deprecated)]
     fn default() -> Self {
         NatsClientOptions {
             username: String::new(),
             password: String::new(),
             tls_required: false,
+            tls_root_ca: None,
+            tls_identity: None,
             auth_token: String::new(),
             verbose: true,
             pedantic: false,
             echo: true,
             name: String::new(),
             cluster_uris: UriVec(Vec::new()),
+            resolver: DnsResolver::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
             ping_interval: 5,
             ping_max_out: 3,
             subscribe_on_reconnect: true,
             ensure_connect: true,
-            reconnect_timeout: 1000,
+            reconnect_policy: ReconnectPolicy::default(),
+            reconnect_timeout: RECONNECT_TIMEOUT_UNSET,
+            reconnect_buffer_size: DEFAULT_RECONNECT_BUFFER_SIZE,
             user_jwt: None,
             nkey: None,
+            user_nkey: None,
+            old_request_style: false,
+            inbox_prefix: DEFAULT_INBOX_PREFIX.into(),
+            pending_limits: PendingLimits::default(),
         }
     }
 }
@@ -85,6 +160,154 @@ impl NatsClientOptions {
     pub fn builder() -> NatsClientOptionsBuilder {
         NatsClientOptionsBuilder::default()
     }
+
+    // Time to wait after the failed reconnect attempt, a fixed delay
+    // if the deprecated `reconnect_timeout` was set and no delay callback is set
+    #[allow(deprecated)]
+    pub(crate) fn reconnect_delay(&self, attempt: u32) -> Duration {
+        if self.reconnect_timeout != RECONNECT_TIMEOUT_UNSET
+            && self.reconnect_policy.delay.is_none()
+        {
+            return Duration::from_millis(self.reconnect_timeout);
+        }
+        self.reconnect_policy.delay_for(attempt)
+    }
+
+    // The nkey signing the server nonce, `user_nkey` or else the seed of the deprecated `nkey`
+    #[allow(deprecated)]
+    pub(crate) fn user_nkey(&self) -> Result<Option<UserNKey>, RatsioError> {
+        if let Some(user_nkey) = self.user_nkey.as_ref() {
+            return Ok(Some(user_nkey.clone()));
+        }
+        match self.nkey.as_deref() {
+            None => Ok(None),
+            #[cfg(feature = "nkey")]
+            Some(seed) => UserNKey::from_seed(seed).map(Some),
+            #[cfg(not(feature = "nkey"))]
+            Some(_) => Err(RatsioError::NKeyError(
+                "signing with a seed needs the `nkey` feature".into(),
+            )),
+        }
+    }
+}
+
+/// Conditions ending the reply stream of `NatsClient::request_many`,
+/// the stream ends on whichever is met first.
+#[derive(Debug, Clone, Copy, Builder, PartialEq)]
+#[builder(setter(into), default)]
+pub struct RequestManyOptions {
+    /// Maximum number of replies, default unlimited
+    pub max_messages: Option<usize>,
+    /// Overall time to wait for replies, default 5 seconds
+    pub timeout: Option<Duration>,
+    /// Maximum time between two replies, default unlimited
+    pub stall_wait: Option<Duration>,
+    /// End the stream on an empty reply, which is not returned. Default false
+    pub sentinel: bool,
+}
+
+impl Default for RequestManyOptions {
+    fn default() -> Self {
+        RequestManyOptions {
+            max_messages: None,
+            timeout: Some(Duration::from_secs(5)),
+            stall_wait: None,
+            sentinel: false,
+        }
+    }
+}
+
+impl RequestManyOptions {
+    pub fn builder() -> RequestManyOptionsBuilder {
+        RequestManyOptionsBuilder::default()
+    }
+}
+
+/// Computes the delay after a failed reconnect attempt, from the attempt number starting at 1.
+pub type ReconnectDelay = Arc<dyn Fn(u32) -> Duration + Send + Sync>;
+
+/// How the client reconnects after losing its connection. An attempt tries every server
+/// of the pool once, the delay before the next attempt doubles from `initial_delay`
+/// up to `max_delay`, plus a random jitter.
+#[derive(Clone, Builder)]
+#[builder(setter(into), default)]
+pub struct ReconnectPolicy {
+    /// Delay after the first failed attempt, default 1 second
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between attempts, default 30 seconds
+    pub max_delay: Duration,
+    /// Upper bound of the random time added to each delay, default 100 milliseconds
+    pub jitter: Duration,
+    /// Attempts before giving up, closing the client with `RatsioError::CannotReconnectToServer`.
+    /// Default None, retrying forever
+    pub max_attempts: Option<u32>,
+    /// Try the servers in a random order on each attempt, default false
+    pub randomize: bool,
+    /// Replaces the exponential backoff and jitter
+    pub delay: Option<ReconnectDelay>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: Duration::from_millis(100),
+            max_attempts: None,
+            randomize: false,
+            delay: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn builder() -> ReconnectPolicyBuilder {
+        ReconnectPolicyBuilder::default()
+    }
+
+    // Time to wait after the failed attempt
+    pub(crate) fn delay_for(&self, attempt: u32) -> Duration {
+        if let Some(delay) = self.delay.as_ref() {
+            return delay(attempt);
+        }
+        let backoff = 2u32
+            .checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.initial_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        let jitter = match self.jitter.as_millis() as u64 {
+            0 => 0,
+            jitter => rand::thread_rng().gen_range(0, jitter + 1),
+        };
+        backoff + Duration::from_millis(jitter)
+    }
+}
+
+impl Debug for ReconnectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ReconnectPolicy")
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("max_attempts", &self.max_attempts)
+            .field("randomize", &self.randomize)
+            .field("delay", &self.delay.as_ref().map(|_| "(func)"))
+            .finish()
+    }
+}
+
+impl PartialEq for ReconnectPolicy {
+    fn eq(&self, other: &ReconnectPolicy) -> bool {
+        self.initial_delay == other.initial_delay
+            && self.max_delay == other.max_delay
+            && self.jitter == other.jitter
+            && self.max_attempts == other.max_attempts
+            && self.randomize == other.randomize
+            && match (self.delay.as_ref(), other.delay.as_ref()) {
+                (Some(delay), Some(other_delay)) => Arc::ptr_eq(delay, other_delay),
+                (None, None) => true,
+                _ => false,
+            }
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -93,14 +316,41 @@ pub enum NatsClientState {
     Connected,
     Reconnecting,
     Disconnected,
+    /// No new messages are received, the buffered ones are being processed before closing
+    Draining,
     Shutdown,
 }
-pub(crate) type DisconnectHandler = Box<dyn Fn(&NatsClient) -> () + Send + Sync>;
+
+/// Connection lifecycle events, see `NatsClient::events`.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// Connected to the server at the given address
+    Connected(String),
+    /// The connection was lost, with the reason
+    Disconnected(String),
+    /// Reconnect attempt, counting from 1
+    Reconnecting(u32),
+    /// Connected again, to the server at the given address
+    Reconnected(String),
+    /// The server is shutting down, the client reconnects to another server once it is gone
+    LameDuck,
+    /// Error sent by the server
+    ServerError(Arc<RatsioError>),
+    /// A subscription does not keep up with its messages
+    SlowConsumer(NatsSid),
+    /// The client was closed, this is the last event
+    Closed,
+}
+pub(crate) type SubscriptionMap = HashMap<String, SubscriptionEntry>;
+pub(crate) type DisconnectHandler = Box<dyn Fn(&NatsClient) + Send + Sync>;
+pub(crate) type ErrorHandler = Box<dyn Fn(&NatsClient, &RatsioError) + Send + Sync>;
+pub use crate::ops::HeaderMap;
 pub use crate::ops::Message as NatsMessage;
 
 pub struct NatsClient {
     inner: Arc<NatsClientInner>,
     disconnect_handlers: RwLock<Vec<DisconnectHandler>>,
+    error_handlers: RwLock<Vec<ErrorHandler>>,
 }
 
 #[derive(Debug)]
@@ -109,15 +359,56 @@ pub(crate) enum ClosableMessage {
     Close,
 }
 
+pub(crate) struct SubscriptionEntry {
+    queue: Arc<PendingQueue>,
+    cmd: Subscribe,
+    /// Number of messages after which the subscription ends, set by auto_unsubscribe
+    max_msgs: Option<u32>,
+    delivered: u32,
+}
+
+// Wildcard subscription shared by all requests of a client,
+// replies are routed to the pending request by the last token of their subject.
+pub(crate) struct RequestMux {
+    subject_prefix: String,
+    sid: String,
+    pending: HashMap<String, ReplySender>,
+}
+
+// Publishes made while reconnecting, sent in order once reconnected
+#[derive(Default)]
+pub(crate) struct ReconnectBuffer {
+    publishes: VecDeque<Publish>,
+    bytes: usize,
+}
+
+pub(crate) enum ReplySender {
+    Single(oneshot::Sender<Message>),
+    Many(futures::channel::mpsc::UnboundedSender<Message>),
+}
+
 pub struct NatsClientInner {
     conn_sink: Arc<Mutex<SplitSink<NatsTcpStream, Op>>>,
     /// Backup of options
     opts: NatsClientOptions,
     /// Server info
     server_info: RwLock<Option<ServerInfo>>,
-    subscriptions: Arc<Mutex<HashMap<String, (UnboundedSender<ClosableMessage>, Subscribe)>>>,
+    /// Servers to reconnect to, the configured ones and those advertised by the cluster
+    server_pool: RwLock<ServerPool>,
+    /// Client certificate used on the next (re)connect
+    tls_identity: RwLock<Option<TlsIdentity>>,
+    subscriptions: Arc<Mutex<SubscriptionMap>>,
+    request_mux: Mutex<Option<RequestMux>>,
+    reconnect_buffer: Mutex<ReconnectBuffer>,
+    /// One entry per PING awaiting its PONG, in sending order
+    pongs: Mutex<VecDeque<Option<oneshot::Sender<()>>>>,
     on_reconnect: tokio::sync::Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>>,
     state: RwLock<NatsClientState>,
+    /// Address of the server the client is connected to
+    connected_server: RwLock<Option<ServerUrl>>,
+    /// Wakes up the reconnect task
+    disconnected: Notify,
+    events: broadcast::Sender<ClientEvent>,
     last_ping: RwLock<u128>,
     client_ref: RwLock<Option<Arc<NatsClient>>>,
     reconnect_version: RwLock<u128>,
@@ -134,6 +425,12 @@ impl ::std::fmt::Debug for NatsClient {
 #[derive(Clone, Debug, PartialEq)]
 pub struct UriVec(Vec<String>);
 
+impl UriVec {
+    pub(crate) fn server_urls(&self) -> Result<Vec<ServerUrl>, RatsioError> {
+        self.0.iter().map(|uri| uri.parse()).collect()
+    }
+}
+
 impl From<String> for NatsClientOptions {
     fn from(uri: String) -> Self {
         NatsClientOptions {
@@ -154,6 +451,7 @@ pub type SignerCallback =
 #[derive(Clone)]
 pub struct UserJWT {
     jwt: String,
+    signer: SignerCallback,
 }
 
 impl Debug for UserJWT {
@@ -171,7 +469,195 @@ impl PartialEq for UserJWT {
 impl UserJWT {
     /// Creates a new UserJWT option from an encoded JWT and a callback to be invoked to sign
     /// the server-provided nonce
-    pub fn new(jwt: String, _signer: SignerCallback) -> UserJWT {
-        UserJWT { jwt }
+    pub fn new(jwt: String, signer: SignerCallback) -> UserJWT {
+        UserJWT { jwt, signer }
+    }
+
+    pub fn jwt(&self) -> &str {
+        &self.jwt
+    }
+
+    /// Signs the server nonce, returning the base64url encoded signature expected in CONNECT.
+    pub(crate) fn sign(&self, nonce: &str) -> Result<String, RatsioError> {
+        match (self.signer)(nonce.as_bytes()) {
+            Ok(signature) => Ok(data_encoding::BASE64URL_NOPAD.encode(&signature)),
+            Err(err) => Err(RatsioError::NonceSigningError(err.to_string())),
+        }
+    }
+}
+
+/// An option that indicates NKey authentication should be used. The public key is sent in
+/// CONNECT along with the server nonce signed by the callback. When built from a seed,
+/// the seed only lives inside the key pair held by the callback and is never printed.
+#[derive(Clone)]
+pub struct UserNKey {
+    public_key: String,
+    signer: SignerCallback,
+}
+
+impl Debug for UserNKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "UserNKey {{ public_key: {}, signer: (func) }}",
+            self.public_key
+        )
+    }
+}
+
+impl PartialEq for UserNKey {
+    fn eq(&self, other: &UserNKey) -> bool {
+        self.public_key == other.public_key
+    }
+}
+
+impl UserNKey {
+    /// Creates a new UserNKey option from a public user NKey and a callback to be invoked to sign
+    /// the server-provided nonce
+    pub fn new(public_key: String, signer: SignerCallback) -> UserNKey {
+        UserNKey { public_key, signer }
+    }
+
+    /// Creates a new UserNKey option from a user seed (`SU...`)
+    #[cfg(feature = "nkey")]
+    pub fn from_seed(seed: &str) -> Result<UserNKey, RatsioError> {
+        let key_pair = nkeys::KeyPair::from_seed(seed)
+            .map_err(|err| RatsioError::NKeyError(err.to_string()))?;
+        let public_key = key_pair.public_key();
+        let signer: SignerCallback = Arc::new(move |nonce| {
+            key_pair
+                .sign(nonce)
+                .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
+        });
+        Ok(UserNKey { public_key, signer })
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Signs the server nonce, returning the base64url encoded signature expected in CONNECT.
+    pub(crate) fn sign(&self, nonce: &str) -> Result<String, RatsioError> {
+        match (self.signer)(nonce.as_bytes()) {
+            Ok(signature) => Ok(data_encoding::BASE64URL_NOPAD.encode(&signature)),
+            Err(err) => Err(RatsioError::NonceSigningError(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_jwt_signs_nonce() {
+        let user_jwt = UserJWT::new(
+            "eyJ0eXAiOiJqd3QifQ".into(),
+            Arc::new(|nonce| Ok(nonce.iter().rev().cloned().collect())),
+        );
+        assert_eq!(user_jwt.jwt(), "eyJ0eXAiOiJqd3QifQ");
+        assert_eq!(user_jwt.sign("abc").unwrap(), "Y2Jh");
+    }
+
+    #[test]
+    fn user_jwt_signing_error() {
+        let user_jwt = UserJWT::new(
+            "eyJ0eXAiOiJqd3QifQ".into(),
+            Arc::new(|_| Err("no seed".into())),
+        );
+        match user_jwt.sign("abc") {
+            Err(RatsioError::NonceSigningError(msg)) => assert_eq!(msg, "no seed"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reconnect_backoff() {
+        let policy = ReconnectPolicy {
+            jitter: Duration::from_millis(0),
+            ..Default::default()
+        };
+        assert_eq!(policy.delay_for(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(4));
+        assert_eq!(policy.delay_for(6), Duration::from_secs(30));
+        assert_eq!(policy.delay_for(100), Duration::from_secs(30));
+
+        let policy = ReconnectPolicy::default();
+        let delay = policy.delay_for(1);
+        assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_millis(1100));
+    }
+
+    #[test]
+    fn reconnect_delay_callback() {
+        let policy = ReconnectPolicy::builder()
+            .delay(Some(
+                Arc::new(|attempt| Duration::from_millis(u64::from(attempt) * 10))
+                    as ReconnectDelay,
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(policy.delay_for(3), Duration::from_millis(30));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_reconnect_timeout() {
+        let mut opts = NatsClientOptions::default();
+        opts.reconnect_policy.jitter = Duration::from_millis(0);
+        assert_eq!(opts.reconnect_delay(3), Duration::from_secs(4));
+
+        opts.reconnect_timeout = 250;
+        assert_eq!(opts.reconnect_delay(3), Duration::from_millis(250));
+        let opts = NatsClientOptions::builder()
+            .reconnect_timeout(1000u64)
+            .build()
+            .unwrap();
+        assert_eq!(opts.reconnect_delay(1), Duration::from_millis(1000));
+        assert_eq!(opts.reconnect_delay(3), Duration::from_millis(1000));
+    }
+
+    #[cfg(feature = "nkey")]
+    #[test]
+    fn user_nkey_from_seed() {
+        let key_pair = nkeys::KeyPair::new_user();
+        let seed = key_pair.seed().unwrap();
+        let user_nkey = UserNKey::from_seed(&seed).unwrap();
+        assert_eq!(user_nkey.public_key(), key_pair.public_key());
+        assert!(!format!("{:?}", user_nkey).contains(&seed));
+
+        let signature = data_encoding::BASE64URL_NOPAD
+            .decode(user_nkey.sign("nonce").unwrap().as_bytes())
+            .unwrap();
+        assert!(key_pair.verify(b"nonce", &signature).is_ok());
+    }
+
+    #[cfg(feature = "nkey")]
+    #[test]
+    fn user_nkey_invalid_seed() {
+        match UserNKey::from_seed("SUNOTASEED") {
+            Err(RatsioError::NKeyError(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "nkey")]
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_nkey_seed() {
+        let key_pair = nkeys::KeyPair::new_user();
+        let opts = NatsClientOptions::builder()
+            .nkey(key_pair.seed().unwrap())
+            .build()
+            .unwrap();
+        let user_nkey = opts.user_nkey().unwrap().unwrap();
+        assert_eq!(user_nkey.public_key(), key_pair.public_key());
+
+        let other = nkeys::KeyPair::new_user();
+        let opts = NatsClientOptions {
+            user_nkey: Some(UserNKey::from_seed(&other.seed().unwrap()).unwrap()),
+            ..opts
+        };
+        let user_nkey = opts.user_nkey().unwrap().unwrap();
+        assert_eq!(user_nkey.public_key(), other.public_key());
     }
 }
@@ -0,0 +1,362 @@
+use crate::error::RatsioError;
+use crate::ops::Message;
+use futures::stream::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use tokio::sync::Notify;
+
+// Defaults matching the other NATS clients
+const DEFAULT_MAX_PENDING_MESSAGES: usize = 512 * 1024;
+const DEFAULT_MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// What to do with incoming messages when a subscription reaches its pending limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlowConsumerPolicy {
+    /// Stop reading from the connection until the subscription catches up.
+    /// This holds back every subscription of the client.
+    Block,
+    /// Discard the incoming message
+    DropNewest,
+    /// Discard the oldest pending messages to make room for the incoming one
+    DropOldest,
+}
+
+/// Limits of the messages received but not yet consumed by a subscription.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingLimits {
+    /// Maximum number of pending messages, None for unlimited
+    pub max_messages: Option<usize>,
+    /// Maximum payload bytes of pending messages, None for unlimited
+    pub max_bytes: Option<usize>,
+    pub policy: SlowConsumerPolicy,
+}
+
+impl Default for PendingLimits {
+    fn default() -> Self {
+        PendingLimits {
+            max_messages: Some(DEFAULT_MAX_PENDING_MESSAGES),
+            max_bytes: Some(DEFAULT_MAX_PENDING_BYTES),
+            policy: SlowConsumerPolicy::DropNewest,
+        }
+    }
+}
+
+impl PendingLimits {
+    /// No limits, the pending messages can grow without bound.
+    pub fn unlimited() -> Self {
+        PendingLimits {
+            max_messages: None,
+            max_bytes: None,
+            policy: SlowConsumerPolicy::DropNewest,
+        }
+    }
+}
+
+/// Messages held by a subscription and not yet consumed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PendingCounts {
+    pub messages: usize,
+    pub bytes: usize,
+    /// Messages discarded so far because of the pending limits
+    pub dropped: u64,
+}
+
+pub(crate) enum Push {
+    Queued,
+    Dropped,
+    /// The queue is full and the policy is to block, try again once there is room
+    Full(Message),
+    Closed,
+}
+
+struct QueueState {
+    messages: VecDeque<Message>,
+    bytes: usize,
+    limits: PendingLimits,
+    closed: bool,
+    receiver_dropped: bool,
+    slow_consumer: bool,
+    dropped: u64,
+    error: Option<RatsioError>,
+    waker: Option<Waker>,
+}
+
+impl QueueState {
+    fn fits(&self, size: usize) -> bool {
+        self.messages.is_empty()
+            || (self
+                .limits
+                .max_messages
+                .map_or(true, |max| self.messages.len() < max)
+                && self
+                    .limits
+                    .max_bytes
+                    .map_or(true, |max| self.bytes + size <= max))
+    }
+
+    fn pop(&mut self) -> Option<Message> {
+        let message = self.messages.pop_front()?;
+        self.bytes -= message.payload.len();
+        Some(message)
+    }
+}
+
+// Buffer between the connection reader and a subscription stream
+pub(crate) struct PendingQueue {
+    state: Mutex<QueueState>,
+    space: Notify,
+    drained: Notify,
+}
+
+impl PendingQueue {
+    pub(crate) fn new(limits: PendingLimits) -> Arc<Self> {
+        Arc::new(PendingQueue {
+            state: Mutex::new(QueueState {
+                messages: VecDeque::new(),
+                bytes: 0,
+                limits,
+                closed: false,
+                receiver_dropped: false,
+                slow_consumer: false,
+                dropped: 0,
+                error: None,
+                waker: None,
+            }),
+            space: Notify::new(),
+            drained: Notify::new(),
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Queue the message following the limits policy.
+    // Also returns whether the subscription just became a slow consumer.
+    pub(crate) fn push(&self, message: Message) -> (Push, bool) {
+        let mut state = self.state();
+        if state.closed || state.receiver_dropped {
+            return (Push::Closed, false);
+        }
+        let size = message.payload.len();
+        if state.fits(size) {
+            state.bytes += size;
+            state.messages.push_back(message);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            return (Push::Queued, false);
+        }
+        let push = match state.limits.policy {
+            SlowConsumerPolicy::Block => Push::Full(message),
+            SlowConsumerPolicy::DropNewest => {
+                state.dropped += 1;
+                Push::Dropped
+            }
+            SlowConsumerPolicy::DropOldest => {
+                while !state.fits(size) {
+                    state.pop();
+                    state.dropped += 1;
+                }
+                state.bytes += size;
+                state.messages.push_back(message);
+                Push::Dropped
+            }
+        };
+        let slow_consumer = !state.slow_consumer;
+        state.slow_consumer = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        (push, slow_consumer)
+    }
+
+    // Wait until a message was consumed or the subscription ended.
+    pub(crate) async fn wait_for_space(&self) {
+        self.space.notified().await
+    }
+
+    // Wait until all pending messages were consumed or the stream was dropped.
+    pub(crate) async fn wait_drained(&self) {
+        loop {
+            let drained = self.drained.notified();
+            {
+                let state = self.state();
+                if state.messages.is_empty() || state.receiver_dropped {
+                    return;
+                }
+            }
+            drained.await;
+        }
+    }
+
+    // End the stream once the pending messages are consumed
+    pub(crate) fn close(&self) {
+        let mut state = self.state();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.space.notify_one();
+    }
+
+    // End the stream because of an error, which is kept for the subscriber
+    pub(crate) fn fail(&self, err: RatsioError) {
+        self.state().error = Some(err);
+        self.close();
+    }
+
+    pub(crate) fn take_error(&self) -> Option<RatsioError> {
+        self.state().error.take()
+    }
+
+    pub(crate) fn set_limits(&self, limits: PendingLimits) {
+        self.state().limits = limits;
+        self.space.notify_one();
+    }
+
+    pub(crate) fn pending(&self) -> PendingCounts {
+        let state = self.state();
+        PendingCounts {
+            messages: state.messages.len(),
+            bytes: state.bytes,
+            dropped: state.dropped,
+        }
+    }
+}
+
+// Stream side of a PendingQueue
+pub(crate) struct PendingReceiver(pub(crate) Arc<PendingQueue>);
+
+impl Stream for PendingReceiver {
+    type Item = Message;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let queue = &self.0;
+        let mut state = queue.state();
+        match state.pop() {
+            Some(message) => {
+                if state.messages.is_empty() {
+                    state.slow_consumer = false;
+                    queue.drained.notify_waiters();
+                }
+                queue.space.notify_one();
+                Poll::Ready(Some(message))
+            }
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for PendingReceiver {
+    fn drop(&mut self) {
+        self.0.state().receiver_dropped = true;
+        self.0.space.notify_one();
+        self.0.drained.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn message(payload: &str) -> Message {
+        Message {
+            payload: payload.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn limits(max_messages: usize, policy: SlowConsumerPolicy) -> PendingLimits {
+        PendingLimits {
+            max_messages: Some(max_messages),
+            max_bytes: None,
+            policy,
+        }
+    }
+
+    async fn payloads(queue: Arc<PendingQueue>) -> Vec<String> {
+        queue.close();
+        PendingReceiver(queue)
+            .map(|message| String::from_utf8(message.payload).unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn drop_newest() {
+        let queue = PendingQueue::new(limits(2, SlowConsumerPolicy::DropNewest));
+        assert!(matches!(queue.push(message("a")), (Push::Queued, false)));
+        assert!(matches!(queue.push(message("b")), (Push::Queued, false)));
+        assert!(matches!(queue.push(message("c")), (Push::Dropped, true)));
+        assert!(matches!(queue.push(message("d")), (Push::Dropped, false)));
+        assert_eq!(
+            queue.pending(),
+            PendingCounts {
+                messages: 2,
+                bytes: 2,
+                dropped: 2
+            }
+        );
+        assert_eq!(payloads(queue).await, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let queue = PendingQueue::new(PendingLimits {
+            max_messages: None,
+            max_bytes: Some(4),
+            policy: SlowConsumerPolicy::DropOldest,
+        });
+        queue.push(message("ab"));
+        queue.push(message("cd"));
+        assert!(matches!(queue.push(message("efg")), (Push::Dropped, true)));
+        assert_eq!(
+            queue.pending(),
+            PendingCounts {
+                messages: 1,
+                bytes: 3,
+                dropped: 2
+            }
+        );
+        assert_eq!(payloads(queue).await, vec!["efg"]);
+    }
+
+    #[tokio::test]
+    async fn block_until_consumed() {
+        let queue = PendingQueue::new(limits(1, SlowConsumerPolicy::Block));
+        queue.push(message("a"));
+        let message = match queue.push(message("b")) {
+            (Push::Full(message), true) => message,
+            _ => unreachable!(),
+        };
+        let mut receiver = PendingReceiver(queue.clone());
+        assert_eq!(receiver.next().await.unwrap().payload, b"a");
+        queue.wait_for_space().await;
+        assert!(matches!(queue.push(message), (Push::Queued, false)));
+    }
+
+    #[tokio::test]
+    async fn wait_until_drained() {
+        let queue = PendingQueue::new(PendingLimits::default());
+        queue.push(message("a"));
+        queue.push(message("b"));
+        queue.close();
+        let mut receiver = PendingReceiver(queue.clone());
+        let consumer = tokio::spawn(async move {
+            while receiver.next().await.is_some() {
+                tokio::task::yield_now().await;
+            }
+        });
+        queue.wait_drained().await;
+        assert_eq!(queue.pending().messages, 0);
+        consumer.await.unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use crate::nats_client::ServerUrl;
+
+/// A server the client may connect to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PooledServer {
+    /// Address as configured or as advertised by the cluster
+    pub url: ServerUrl,
+    /// Whether the server was learned from the `connect_urls` of a server INFO
+    /// rather than configured in `NatsClientOptions::cluster_uris`
+    pub discovered: bool,
+    /// Failed connection attempts since the last successful one
+    pub failed_attempts: u32,
+}
+
+// Configured servers followed by the ones advertised by the cluster
+pub(crate) struct ServerPool {
+    servers: Vec<PooledServer>,
+}
+
+impl ServerPool {
+    pub(crate) fn new(cluster_urls: &[ServerUrl]) -> Self {
+        let mut pool = ServerPool {
+            servers: Vec::new(),
+        };
+        for url in cluster_urls {
+            if !pool.contains(url) {
+                pool.servers.push(PooledServer {
+                    url: url.clone(),
+                    discovered: false,
+                    failed_attempts: 0,
+                });
+            }
+        }
+        pool
+    }
+
+    fn contains(&self, url: &ServerUrl) -> bool {
+        self.servers
+            .iter()
+            .any(|server| server.url.same_server(url))
+    }
+
+    // Merge the servers currently advertised by the cluster. Discovered servers
+    // no longer advertised are removed, configured ones are always kept.
+    // Discovered servers inherit the credentials and host name of the configured ones.
+    // An empty list, from a standalone server or an INFO update, leaves the pool as is.
+    // Returns whether the pool changed.
+    pub(crate) fn update(&mut self, connect_urls: &[String]) -> bool {
+        if connect_urls.is_empty() {
+            return false;
+        }
+        let configured: Vec<ServerUrl> = self
+            .servers
+            .iter()
+            .filter(|server| !server.discovered)
+            .map(|server| server.url.clone())
+            .collect();
+        let connect_urls: Vec<ServerUrl> = connect_urls
+            .iter()
+            .filter_map(|url| match url.parse::<ServerUrl>() {
+                Ok(url) => Some(url.inherit(&configured)),
+                Err(err) => {
+                    warn!("Ignoring the server advertised by the cluster => {}", err);
+                    None
+                }
+            })
+            .collect();
+        let before = self.servers.len();
+        self.servers.retain(|server| {
+            !server.discovered || connect_urls.iter().any(|url| server.url.same_server(url))
+        });
+        let mut changed = self.servers.len() != before;
+        for url in connect_urls {
+            if !self.contains(&url) {
+                self.servers.push(PooledServer {
+                    url,
+                    discovered: true,
+                    failed_attempts: 0,
+                });
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // Count a failed connection attempt to the server, or reset the count once connected
+    pub(crate) fn record_attempt(&mut self, url: &ServerUrl, connected: bool) {
+        if let Some(server) = self.servers.iter_mut().find(|server| &server.url == url) {
+            if connected {
+                server.failed_attempts = 0;
+            } else {
+                server.failed_attempts += 1;
+            }
+        }
+    }
+
+    pub(crate) fn servers(&self) -> Vec<PooledServer> {
+        self.servers.clone()
+    }
+
+    pub(crate) fn urls(&self) -> Vec<ServerUrl> {
+        self.servers
+            .iter()
+            .map(|server| server.url.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uris(xs: &[&str]) -> Vec<String> {
+        xs.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn url(x: &str) -> ServerUrl {
+        x.parse().unwrap()
+    }
+
+    fn urls(xs: &[&str]) -> Vec<ServerUrl> {
+        xs.iter().map(|x| url(x)).collect()
+    }
+
+    fn server_pool(xs: &[&str]) -> ServerPool {
+        ServerPool::new(&urls(xs))
+    }
+
+    #[test]
+    fn merge_connect_urls() {
+        let mut pool = server_pool(&["nats://10.0.0.1:4222", "10.0.0.1:4222"]);
+        assert_eq!(pool.urls(), urls(&["nats://10.0.0.1:4222"]));
+
+        assert!(pool.update(&uris(&["10.0.0.1:4222", "10.0.0.2:4222"])));
+        assert!(!pool.update(&uris(&["10.0.0.2:4222", "10.0.0.1:4222"])));
+        assert_eq!(
+            pool.servers(),
+            vec![
+                PooledServer {
+                    url: url("nats://10.0.0.1:4222"),
+                    discovered: false,
+                    failed_attempts: 0
+                },
+                PooledServer {
+                    url: url("10.0.0.2:4222"),
+                    discovered: true,
+                    failed_attempts: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_gone_servers() {
+        let mut pool = server_pool(&["10.0.0.1:4222"]);
+        pool.update(&uris(&["10.0.0.2:4222", "10.0.0.3:4222"]));
+        assert!(pool.update(&uris(&["10.0.0.3:4222"])));
+        assert_eq!(pool.urls(), urls(&["10.0.0.1:4222", "10.0.0.3:4222"]));
+
+        // Configured servers stay even when not advertised
+        assert!(pool.update(&uris(&["10.0.0.4:4222"])));
+        assert_eq!(pool.urls(), urls(&["10.0.0.1:4222", "10.0.0.4:4222"]));
+
+        // An INFO without connect_urls keeps the discovered servers
+        assert!(!pool.update(&[]));
+        assert_eq!(pool.urls(), urls(&["10.0.0.1:4222", "10.0.0.4:4222"]));
+    }
+
+    #[test]
+    fn count_failed_attempts() {
+        let mut pool = server_pool(&["10.0.0.1:4222", "10.0.0.2:4222"]);
+        pool.record_attempt(&url("10.0.0.1:4222"), false);
+        pool.record_attempt(&url("10.0.0.1:4222"), false);
+        pool.record_attempt(&url("10.0.0.2:4222"), true);
+        let failed: Vec<u32> = pool
+            .servers()
+            .iter()
+            .map(|server| server.failed_attempts)
+            .collect();
+        assert_eq!(failed, vec![2, 0]);
+
+        pool.record_attempt(&url("10.0.0.1:4222"), true);
+        assert_eq!(pool.servers()[0].failed_attempts, 0);
+    }
+
+    #[test]
+    fn inherit_configured_credentials() {
+        let mut pool = server_pool(&["10.0.0.1:4222", "tls://user:pw@nats.example.com"]);
+        pool.update(&uris(&["10.0.0.2:4222", "nats-b.example.com:4222"]));
+        let servers = pool.servers();
+        let by_ip = &servers[2].url;
+        assert_eq!(by_ip.host(), "10.0.0.2");
+        assert_eq!(by_ip.tls_name(), "nats.example.com");
+        assert_eq!(by_ip.username(), Some("user"));
+        assert_eq!(by_ip.password(), Some("pw"));
+        assert!(by_ip.tls_required());
+        let by_name = &servers[3].url;
+        assert_eq!(by_name.tls_name(), "nats-b.example.com");
+        assert_eq!(by_name.username(), Some("user"));
+        assert!(by_name.tls_required());
+
+        let mut pool = server_pool(&["nats://s3cr3t@10.0.0.1:4222"]);
+        pool.update(&uris(&["10.0.0.2:4222"]));
+        let discovered = &pool.servers()[1].url;
+        assert_eq!(discovered.token(), Some("s3cr3t"));
+        assert_eq!(discovered.tls_name(), "10.0.0.2");
+        assert!(!discovered.tls_required());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::RatsioError;
+use crate::nats_client::pending::{PendingCounts, PendingReceiver};
+use crate::nats_client::{NatsClientInner, NatsSid};
+use crate::ops::Message;
+use futures::stream::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Stream of the messages of a subscription.
+/// The subscription is removed from the server when it is dropped.
+pub struct Subscription {
+    sid: NatsSid,
+    subject: String,
+    receiver: PendingReceiver,
+    inner: Arc<NatsClientInner>,
+    active: bool,
+}
+
+impl Subscription {
+    pub(in crate::nats_client) fn new(
+        sid: NatsSid,
+        subject: String,
+        receiver: PendingReceiver,
+        inner: Arc<NatsClientInner>,
+    ) -> Self {
+        Subscription {
+            sid,
+            subject,
+            receiver,
+            inner,
+            active: true,
+        }
+    }
+
+    pub fn sid(&self) -> &NatsSid {
+        &self.sid
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Messages received and not yet consumed from the stream.
+    pub fn pending(&self) -> PendingCounts {
+        self.receiver.0.pending()
+    }
+
+    /// The error that ended the stream, such as `RatsioError::SubscribePermissionViolation`.
+    pub fn take_error(&mut self) -> Option<RatsioError> {
+        self.receiver.0.take_error()
+    }
+
+    pub async fn unsubscribe(mut self) -> Result<(), RatsioError> {
+        self.active = false;
+        self.inner.un_subscribe(self.sid.clone()).await
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if self.active {
+            let inner = self.inner.clone();
+            let sid = self.sid.clone();
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    let _ = inner.un_subscribe(sid).await;
+                });
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("sid", &self.sid)
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
@@ -1,2 +1,3 @@
 pub mod connection;
 pub mod nats_tcp_stream;
+pub mod resolver;
@@ -0,0 +1,78 @@
+use futures::future::BoxFuture;
+use std::fmt::{Debug, Error, Formatter};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Resolves the host of a server url to the addresses to connect to.
+/// Called on every connect attempt, so that a server moving to new addresses is followed.
+pub trait Resolver: Send + Sync {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>>;
+}
+
+/// Resolver using the system configuration, without blocking the runtime.
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        let host = host.to_string();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), port)).await?;
+            Ok(addrs.collect())
+        })
+    }
+}
+
+/// The `Resolver` used by a client, the `SystemResolver` by default.
+#[derive(Clone)]
+pub struct DnsResolver(Arc<dyn Resolver>);
+
+impl DnsResolver {
+    pub fn new<R>(resolver: R) -> Self
+    where
+        R: Resolver + 'static,
+    {
+        DnsResolver(Arc::new(resolver))
+    }
+
+    pub(crate) async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self.0.resolve(host, port).await
+    }
+}
+
+lazy_static! {
+    // Shared so that default options compare equal
+    static ref SYSTEM_RESOLVER: Arc<dyn Resolver> = Arc::new(SystemResolver);
+}
+
+impl Default for DnsResolver {
+    fn default() -> Self {
+        DnsResolver(SYSTEM_RESOLVER.clone())
+    }
+}
+
+impl Debug for DnsResolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "DnsResolver")
+    }
+}
+
+impl PartialEq for DnsResolver {
+    fn eq(&self, other: &DnsResolver) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolve_ip_literals() {
+        let resolver = DnsResolver::default();
+        let addrs = resolver.resolve("127.0.0.1", 4222).await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:4222".parse().unwrap()]);
+        let addrs = resolver.resolve("::1", 4222).await.unwrap();
+        assert_eq!(addrs, vec!["[::1]:4222".parse().unwrap()]);
+    }
+}
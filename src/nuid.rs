@@ -22,7 +22,7 @@ use rand::Rng;
 use std::sync::Mutex;
 
 const BASE: usize = 62;
-const ALPHABET: [u8; BASE as usize] = [
+const ALPHABET: [u8; BASE] = [
     b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'A', b'B', b'C', b'D', b'E', b'F',
     b'G', b'H', b'I', b'J', b'K', b'L', b'M', b'N', b'O', b'P', b'Q', b'R', b'S', b'T', b'U', b'V',
     b'W', b'X', b'Y', b'Z', b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i', b'j', b'k', b'l',
@@ -178,7 +178,7 @@ mod tests {
     fn unique() {
         let mut set = HashSet::new();
         for _ in 0..100_000 {
-            assert_eq!(set.insert(next()), true);
+            assert!(set.insert(next()));
         }
     }
 }
@@ -2,7 +2,7 @@ use crate::error::RatsioError;
 use crate::nuid::NUID;
 use ::std::fmt;
 use bytes::{BufMut, Bytes, BytesMut};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::From;
 
 #[derive(Debug, PartialEq)]
@@ -86,6 +86,8 @@ macro_rules! get_json_boolean {
 /// * tls_required: If this is set, then the client must perform the TLS/1.2 handshake. Note, this used to be ssl_required and has been updated along with the protocol from SSL to TLS.
 /// * tls_verify: If this is set, the client must provide a valid certificate during the TLS handshake.
 /// * connect_urls : An optional list of server urls that a client can connect to.
+/// * headers: If this is set, the server supports message headers (HPUB / HMSG).
+/// * ldm: If this is set, the server is in lame duck mode and will soon shut down.
 ///
 ///
 #[derive(Clone, Debug, PartialEq)]
@@ -103,6 +105,8 @@ pub struct ServerInfo {
     pub tls_verify: bool,
     pub connect_urls: Vec<String>,
     pub nonce: String,
+    pub headers: bool,
+    pub lame_duck_mode: bool,
 }
 
 impl Default for ServerInfo {
@@ -121,12 +125,14 @@ impl Default for ServerInfo {
             tls_verify: false,
             connect_urls: Vec::new(),
             nonce: "".to_string(),
+            headers: false,
+            lame_duck_mode: false,
         }
     }
 }
 
-impl ToString for ServerInfo {
-    fn to_string(&self) -> String {
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut buff = String::default();
         buff.push_str(&format!(r#"{{"server_id": "{}""#, &self.server_id));
         buff.push_str(&format!(r#","version": "{}""#, self.version));
@@ -157,9 +163,13 @@ impl ToString for ServerInfo {
             buff.push_str(r#","]"#);
         }
         buff.push_str(&format!(r#","nonce": {}"#, self.nonce));
+        buff.push_str(&format!(r#","headers": {}"#, self.headers));
+        if self.lame_duck_mode {
+            buff.push_str(r#","ldm": true"#);
+        }
 
         buff.push('}');
-        buff
+        f.write_str(&buff)
     }
 }
 
@@ -191,6 +201,8 @@ impl From<JsonValue> for ServerInfo {
                     tls_verify: get_json_boolean!(obj, "tls_verify", false),
                     connect_urls,
                     nonce: get_json_string!(obj, "nonce"),
+                    headers: get_json_boolean!(obj, "headers", false),
+                    lame_duck_mode: get_json_boolean!(obj, "ldm", false),
                 }
             }
             _ => ServerInfo::default(),
@@ -216,6 +228,8 @@ impl From<JsonValue> for ServerInfo {
 /// * sig: A signature produced from the nonce the server sent with its INFO message (if using JWT security)
 /// * protocol: optional int. Sending 0 (or absent) indicates client supports original protocol. Sending 1 indicates that the client supports dynamic reconfiguration of cluster topology changes by asynchronously receiving INFO messages with known servers it can reconnect to.
 /// * echo: Optional boolean. If set to true, the server (version 1.2.0+) will not send originating messages from this connection to its own subscriptions. Clients should set this to true only for server supporting this feature, which is when proto in the INFO protocol is set to at least 1.
+/// * headers: Optional boolean. Indicates the client supports message headers, only set when the server advertises them in INFO.
+/// * no_responders: Optional boolean. Requires headers, the server answers a request without subscribers with a 503 status message.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Connect {
     pub verbose: bool,
@@ -232,12 +246,14 @@ pub struct Connect {
     pub sig: Option<String>,
     pub jwt: Option<String>,
     pub nkey: Option<String>,
+    pub headers: bool,
+    pub no_responders: bool,
 }
 
 impl Connect {}
 
-impl ToString for Connect {
-    fn to_string(&self) -> String {
+impl fmt::Display for Connect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut buff = String::default();
         buff.push_str("{\"verbose\": ");
         buff.push_str(&self.verbose.to_string());
@@ -268,8 +284,14 @@ impl ToString for Connect {
         if let Some(ref nkey) = self.nkey {
             buff.push_str(&format!(r#","nkey": "{}""#, nkey));
         }
+        if self.headers {
+            buff.push_str(r#","headers": true"#);
+        }
+        if self.no_responders {
+            buff.push_str(r#","no_responders": true"#);
+        }
         buff.push('}');
-        buff
+        f.write_str(&buff)
     }
 }
 
@@ -290,6 +312,8 @@ impl Default for Connect {
             sig: None,
             jwt: None,
             nkey: None,
+            headers: false,
+            no_responders: false,
         }
     }
 }
@@ -312,12 +336,150 @@ impl From<JsonValue> for Connect {
                 sig: get_json_opt_string!(obj, "sig"),
                 jwt: get_json_opt_string!(obj, "jwt"),
                 nkey: get_json_opt_string!(obj, "nkey"),
+                headers: get_json_boolean!(obj, "headers", false),
+                no_responders: get_json_boolean!(obj, "no_responders", false),
             },
             _ => Connect::default(),
         }
     }
 }
 
+const HEADER_VERSION: &str = "NATS/1.0";
+
+/// Message headers, sent with HPUB and delivered with HMSG (NATS 2.2+).
+///
+/// NATS/1.0[ <status>[ <description>]]\r\n[<key>: <value>\r\n]*\r\n
+///
+/// The status line is only set by the server, e.g. `503` for a request without responders.
+/// A key may hold several values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeaderMap {
+    pub status: Option<u16>,
+    pub description: Option<String>,
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `key` to a single value, replacing any previous values.
+    /// Fails with `RatsioError::InvalidHeader` on a key or value breaking the header block.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Result<(), RatsioError>
+    where
+        K: ToString,
+        V: ToString,
+    {
+        let (key, value) = Self::checked(key.to_string(), value.to_string())?;
+        self.entries.insert(key, vec![value]);
+        Ok(())
+    }
+
+    /// Adds a value to `key`, keeping previous values.
+    /// Fails with `RatsioError::InvalidHeader` on a key or value breaking the header block.
+    pub fn append<K, V>(&mut self, key: K, value: V) -> Result<(), RatsioError>
+    where
+        K: ToString,
+        V: ToString,
+    {
+        let (key, value) = Self::checked(key.to_string(), value.to_string())?;
+        self.entries.entry(key).or_default().push(value);
+        Ok(())
+    }
+
+    // Keys and values are written as `key: value\r\n` lines
+    fn checked(key: String, value: String) -> Result<(String, String), RatsioError> {
+        let line_break = |text: &str| text.contains(['\r', '\n']);
+        if key.is_empty() || key.contains(':') || line_break(&key) {
+            return Err(RatsioError::InvalidHeader(format!("bad key {:?}", key)));
+        }
+        if line_break(&value) {
+            return Err(RatsioError::InvalidHeader(format!("bad value for {}", key)));
+        }
+        Ok((key, value))
+    }
+
+    /// First value of `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .and_then(|values| values.first())
+            .map(|value| value.as_str())
+    }
+
+    /// All values of `key`
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.entries
+            .get(key)
+            .map(|values| values.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<String>> {
+        self.entries.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().flat_map(|(key, values)| {
+            values
+                .iter()
+                .map(move |value| (key.as_str(), value.as_str()))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buff = String::from(HEADER_VERSION);
+        if let Some(status) = self.status {
+            buff.push_str(&format!(" {}", status));
+            if let Some(ref description) = self.description {
+                buff.push_str(&format!(" {}", description));
+            }
+        }
+        buff.push_str("\r\n");
+        for (key, value) in self.iter() {
+            buff.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        buff.push_str("\r\n");
+        buff.into_bytes()
+    }
+}
+
+impl From<&[u8]> for HeaderMap {
+    /// Lenient parsing of a header block, lines that are not `key: value` are skipped.
+    fn from(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let mut lines = text.split("\r\n");
+        let mut headers = HeaderMap::new();
+        if let Some(status_line) = lines.next() {
+            let mut status = status_line
+                .trim_start_matches(HEADER_VERSION)
+                .trim()
+                .splitn(2, ' ');
+            headers.status = status.next().and_then(|code| code.parse().ok());
+            headers.description = status
+                .next()
+                .map(|description| description.trim().to_string())
+                .filter(|description| !description.is_empty());
+        }
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let mut key_value = line.splitn(2, ':');
+            if let (Some(key), Some(value)) = (key_value.next(), key_value.next()) {
+                let _ = headers.append(key.trim(), value.trim());
+            }
+        }
+        headers
+    }
+}
+
 /// MSG  protocol message is used to deliver an application message to the client.
 /// MSG <subject> <sid> [reply-to] <#bytes>\r\n[payload]\r\n
 ///
@@ -329,40 +491,48 @@ impl From<JsonValue> for Connect {
 /// * #bytes: Size of the payload in bytes
 /// * payload: The message payload data
 ///
-#[derive(Clone, PartialEq)]
+/// Messages with headers are delivered with HMSG instead
+/// HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+///
+#[derive(Clone, Default, PartialEq)]
 pub struct Message {
     pub subject: String,
     pub sid: String,
     pub reply_to: Option<String>,
     pub payload: Vec<u8>,
+    pub headers: Option<HeaderMap>,
+}
+
+impl Message {
+    /// A status only message sent by the server in reply to a request nobody subscribes to
+    pub fn is_no_responders(&self) -> bool {
+        self.payload.is_empty()
+            && self
+                .headers
+                .as_ref()
+                .is_some_and(|headers| headers.status == Some(503))
+    }
 }
 
 impl fmt::Debug for Message {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Message {{ subject: {}, sid: {}, reply_to: {:?} }}",
-            self.subject, self.sid, self.reply_to
+            "Message {{ subject: {}, sid: {}, reply_to: {:?}, headers: {:?} }}",
+            self.subject, self.sid, self.reply_to, self.headers
         )
     }
 }
 
-impl Default for Message {
-    fn default() -> Self {
-        Message {
-            subject: String::from(""),
-            sid: String::from(""),
-            reply_to: None,
-            payload: Vec::new(),
-        }
-    }
-}
-
-#[derive(Clone, Debug, PartialEq)]
+/// PUB <subject> [reply-to] <#bytes>\r\n[payload]\r\n
+/// or, when headers are set
+/// HPUB <subject> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Publish {
     pub subject: String,
     pub reply_to: Option<String>,
     pub payload: Vec<u8>,
+    pub headers: Option<HeaderMap>,
 }
 
 impl Publish {
@@ -371,16 +541,6 @@ impl Publish {
     }
 }
 
-impl Default for Publish {
-    fn default() -> Self {
-        Publish {
-            subject: String::from(""),
-            reply_to: None,
-            payload: Vec::new(),
-        }
-    }
-}
-
 #[derive(Clone, Debug, PartialEq)]
 pub struct Subscribe {
     pub subject: String,
@@ -404,7 +564,7 @@ impl Subscribe {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct UnSubscribe {
     pub sid: String,
     pub max_msgs: Option<u32>,
@@ -412,15 +572,6 @@ pub struct UnSubscribe {
 
 impl UnSubscribe {}
 
-impl Default for UnSubscribe {
-    fn default() -> Self {
-        UnSubscribe {
-            sid: String::from(""),
-            max_msgs: None,
-        }
-    }
-}
-
 /*
 INFO	Server	Sent to client after initial TCP/IP connection
 CONNECT	Client	Sent to server to specify connection information
@@ -428,6 +579,8 @@ PUB	Client	Publish a message to a subject, with optional reply subject
 SUB	Client	Subscribe to a subject (or subject wildcard)
 UNSUB	Client	Unsubscribe (or auto-unsubscribe) from subject
 MSG	Server	Delivers a message payload to a subscriber
+HPUB	Client	Publish a message with headers
+HMSG	Server	Delivers a message with headers to a subscriber
 PING	Both	PING keep-alive message
 PONG	Both	PONG keep-alive response
 +OK	Server	Acknowledges well-formed protocol message in verbose mode
@@ -449,7 +602,7 @@ pub enum Op {
 }
 
 #[inline]
-fn extend_bytes<'a>(dst: &'a mut BytesMut, s: &[u8]) {
+fn extend_bytes(dst: &mut BytesMut, s: &[u8]) {
     let buf_len = s.len();
     if dst.remaining_mut() < buf_len {
         dst.reserve(buf_len);
@@ -457,6 +610,25 @@ fn extend_bytes<'a>(dst: &'a mut BytesMut, s: &[u8]) {
     dst.put(s);
 }
 
+// Writes `<#bytes>\r\n[payload]\r\n`, or `<#header bytes> <#total bytes>\r\n[headers][payload]\r\n`
+#[inline]
+fn extend_sizes_and_payload(dst: &mut BytesMut, headers: Option<Vec<u8>>, payload: &[u8]) {
+    match headers {
+        Some(headers) => {
+            extend_bytes(
+                dst,
+                format!("\t{}\t{}\r\n", headers.len(), headers.len() + payload.len()).as_bytes(),
+            );
+            extend_bytes(dst, &headers[..]);
+        }
+        None => {
+            extend_bytes(dst, format!("\t{}\r\n", payload.len()).as_bytes());
+        }
+    }
+    extend_bytes(dst, payload);
+    extend_bytes(dst, &b"\r\n"[..]);
+}
+
 impl Op {
     pub fn into_bytes(self) -> Result<Bytes, RatsioError> {
         match self {
@@ -506,7 +678,12 @@ impl Op {
             }
             Op::MSG(msg) => {
                 let mut dst = BytesMut::new();
-                extend_bytes(&mut dst, &b"MSG\t"[..]);
+                let headers = msg.headers.map(|headers| headers.to_bytes());
+                if headers.is_some() {
+                    extend_bytes(&mut dst, &b"HMSG\t"[..]);
+                } else {
+                    extend_bytes(&mut dst, &b"MSG\t"[..]);
+                }
                 extend_bytes(&mut dst, msg.subject.as_bytes());
                 extend_bytes(&mut dst, &b"\t"[..]);
                 extend_bytes(&mut dst, msg.sid.as_bytes());
@@ -514,25 +691,23 @@ impl Op {
                     extend_bytes(&mut dst, &b"\t"[..]);
                     extend_bytes(&mut dst, reply_to.as_bytes());
                 }
-                extend_bytes(&mut dst, format!("\t{}\r\n", msg.payload.len()).as_bytes());
-                extend_bytes(&mut dst, &msg.payload[..]);
-                extend_bytes(&mut dst, &b"\r\n"[..]);
+                extend_sizes_and_payload(&mut dst, headers, &msg.payload[..]);
                 Ok(dst.freeze())
             }
             Op::PUB(publish) => {
                 let mut dst = BytesMut::new();
-                extend_bytes(&mut dst, &b"PUB\t"[..]);
+                let headers = publish.headers.map(|headers| headers.to_bytes());
+                if headers.is_some() {
+                    extend_bytes(&mut dst, &b"HPUB\t"[..]);
+                } else {
+                    extend_bytes(&mut dst, &b"PUB\t"[..]);
+                }
                 extend_bytes(&mut dst, publish.subject.as_bytes());
                 if let Some(reply_to) = publish.reply_to {
                     extend_bytes(&mut dst, &b"\t"[..]);
                     extend_bytes(&mut dst, reply_to.as_bytes());
                 }
-                extend_bytes(
-                    &mut dst,
-                    format!("\t{}\r\n", publish.payload.len()).as_bytes(),
-                );
-                extend_bytes(&mut dst, &publish.payload[..]);
-                extend_bytes(&mut dst, &b"\r\n"[..]);
+                extend_sizes_and_payload(&mut dst, headers, &publish.payload[..]);
                 Ok(dst.freeze())
             }
             Op::SUB(sub) => {
@@ -575,7 +750,7 @@ fn ser_ok() {
             assert_eq!(&b[..], b"+OK\r\n");
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -587,7 +762,7 @@ fn ser_ping() {
             assert_eq!(&b[..], b"PING\r\n");
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -599,7 +774,7 @@ fn ser_pong() {
             assert_eq!(&b[..], b"PONG\r\n");
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -621,6 +796,8 @@ fn ser_connect() {
         sig: None,
         jwt: None,
         nkey: None,
+        headers: false,
+        no_responders: false,
     })
     .into_bytes()
     {
@@ -637,7 +814,7 @@ fn ser_connect() {
             assert_eq!(&b[..], c.as_bytes());
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -649,6 +826,7 @@ fn ser_message() {
         sid: String::from("9"),
         reply_to: Some(String::from("INBOX.34")),
         payload: Vec::from(b"Hello World" as &[u8]),
+        headers: None,
     })
     .into_bytes()
     {
@@ -658,7 +836,7 @@ fn ser_message() {
             assert_eq!(&b[..], c.as_bytes());
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -670,6 +848,7 @@ fn ser_message_no_reply() {
         sid: String::from("9"),
         reply_to: None,
         payload: Vec::from(b"Hello New World" as &[u8]),
+        headers: None,
     })
     .into_bytes()
     {
@@ -679,7 +858,7 @@ fn ser_message_no_reply() {
             assert_eq!(&b[..], c.as_bytes());
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -690,6 +869,7 @@ fn ser_publish() {
         subject: String::from("FRONT.DOOR"),
         reply_to: Some(String::from("INBOX.22")),
         payload: Vec::from(b"Knock Knock" as &[u8]),
+        headers: None,
     })
     .into_bytes()
     {
@@ -701,7 +881,7 @@ fn ser_publish() {
             );
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -712,6 +892,7 @@ fn ser_publish_no_reply() {
         subject: String::from("FRONT.DOOR"),
         reply_to: None,
         payload: Vec::from(b"Knock Knock Again" as &[u8]),
+        headers: None,
     })
     .into_bytes()
     {
@@ -720,11 +901,116 @@ fn ser_publish_no_reply() {
             assert_eq!(&b[..], &b"PUB\tFRONT.DOOR\t17\r\nKnock Knock Again\r\n"[..]);
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
+        }
+    }
+}
+
+#[test]
+fn ser_publish_with_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("Nats-Msg-Id", "1").unwrap();
+    match Op::PUB(Publish {
+        subject: String::from("FRONT.DOOR"),
+        reply_to: Some(String::from("INBOX.22")),
+        payload: Vec::from(b"Knock Knock" as &[u8]),
+        headers: Some(headers),
+    })
+    .into_bytes()
+    {
+        Ok(b) => {
+            assert_eq!(
+                &b[..],
+                &b"HPUB\tFRONT.DOOR\tINBOX.22\t28\t39\r\nNATS/1.0\r\nNats-Msg-Id: 1\r\n\r\nKnock Knock\r\n"[..]
+            );
+        }
+        Err(_) => {
+            unreachable!();
+        }
+    }
+}
+
+#[test]
+fn ser_message_with_headers() {
+    let mut headers = HeaderMap::new();
+    headers.status = Some(503);
+    match Op::MSG(Message {
+        subject: String::from("FOO.BAR"),
+        sid: String::from("9"),
+        reply_to: None,
+        payload: Vec::new(),
+        headers: Some(headers),
+    })
+    .into_bytes()
+    {
+        Ok(b) => {
+            assert_eq!(
+                &b[..],
+                &b"HMSG\tFOO.BAR\t9\t16\t16\r\nNATS/1.0 503\r\n\r\n\r\n"[..]
+            );
+        }
+        Err(_) => {
+            unreachable!();
         }
     }
 }
 
+#[test]
+fn no_responders_message() {
+    let mut headers = HeaderMap::new();
+    headers.status = Some(503);
+    let message = Message {
+        headers: Some(headers),
+        ..Default::default()
+    };
+    assert!(message.is_no_responders());
+    assert!(!Message::default().is_no_responders());
+}
+
+#[test]
+fn header_map_round_trip() {
+    let mut headers = HeaderMap::new();
+    headers.append("X-Trace", "a").unwrap();
+    headers.append("X-Trace", "b").unwrap();
+    headers.insert("Nats-Msg-Id", "42").unwrap();
+    let parsed = HeaderMap::from(&headers.to_bytes()[..]);
+    assert_eq!(parsed, headers);
+    assert_eq!(parsed.get("Nats-Msg-Id"), Some("42"));
+    assert_eq!(
+        parsed.get_all("X-Trace"),
+        &["a".to_string(), "b".to_string()]
+    );
+
+    let status = HeaderMap::from(&b"NATS/1.0 503 No Responders\r\n\r\n"[..]);
+    assert_eq!(status.status, Some(503));
+    assert_eq!(status.description, Some("No Responders".to_string()));
+    assert!(status.is_empty());
+}
+
+#[test]
+fn header_map_rejects_line_breaks() {
+    let mut headers = HeaderMap::new();
+    for (key, value) in [
+        ("X-Trace\r\nX-Other", "a"),
+        ("X-Trace:", "a"),
+        ("", "a"),
+        ("X-Trace", "a\r\n\r\nPUB foo 0"),
+        ("X-Trace", "a\nb"),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            headers.insert(key, value),
+            Err(RatsioError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            headers.append(key, value),
+            Err(RatsioError::InvalidHeader(_))
+        ));
+    }
+    assert!(headers.is_empty());
+}
+
 #[test]
 fn ser_sub() {
     match Op::SUB(Subscribe {
@@ -739,7 +1025,7 @@ fn ser_sub() {
             assert_eq!(&b[..], &b"SUB\tBAR\tG1\t44\r\n"[..]);
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -758,7 +1044,7 @@ fn ser_sub_no_group() {
             assert_eq!(&b[..], &b"SUB\tBAR\t44\r\n"[..]);
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -776,7 +1062,7 @@ fn ser_unsub() {
             assert_eq!(&b[..], &b"UNSUB\t44234535\t500\r\n"[..]);
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -794,7 +1080,7 @@ fn ser_unsub_no_max() {
             assert_eq!(&b[..], &b"UNSUB\t44234535\r\n"[..]);
         }
         Err(_) => {
-            assert!(false);
+            unreachable!();
         }
     }
 }
@@ -253,6 +253,7 @@ named!(
                         sid,
                         reply_to,
                         payload: Vec::new(),
+                        headers: None,
                     },
                 )
             }
@@ -263,6 +264,7 @@ named!(
                 sid: item.1.sid,
                 reply_to: item.1.reply_to,
                 payload: Vec::from(payload),
+                headers: None,
             })
     )
 );
@@ -296,6 +298,7 @@ named!(
                         subject,
                         reply_to,
                         payload: Vec::new(),
+                        headers: None,
                     },
                 )
             }
@@ -305,6 +308,120 @@ named!(
                 subject: item.1.subject,
                 reply_to: item.1.reply_to,
                 payload: Vec::from(payload),
+                headers: None,
+            })
+    )
+);
+
+// Splits a HMSG / HPUB body into its header block and payload
+fn split_headers(body: &[u8], header_size: usize) -> (Option<HeaderMap>, Vec<u8>) {
+    let header_size = header_size.min(body.len());
+    (
+        Some(HeaderMap::from(&body[..header_size])),
+        Vec::from(&body[header_size..]),
+    )
+}
+
+// Header and total sizes ending a HMSG / HPUB control line,
+// None when missing, not numbers or the headers do not fit
+fn header_sizes(tokens: &[String]) -> Option<(usize, usize)> {
+    let token_len = tokens.len();
+    if token_len < 2 {
+        return None;
+    }
+    let size: usize = tokens[token_len - 1].parse().ok()?;
+    let header_size: usize = tokens[token_len - 2].parse().ok()?;
+    if header_size > size {
+        return None;
+    }
+    Some((size, header_size))
+}
+
+//HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+named!(
+    header_message<Message>,
+    do_parse!(
+        item: map_opt!(
+            delimited!(
+                leading_ws!(take_while!(is_space)),
+                separated_list!(take_while!(is_space), text_token),
+                leading_space!(tag!("\r\n"))
+            ),
+            |tokens: Vec<String>| {
+                let token_len = tokens.len();
+                if token_len != 4 && token_len != 5 {
+                    return None;
+                }
+                let (size, header_size) = header_sizes(&tokens)?;
+                let reply_to = if token_len > 4 {
+                    Some(tokens[2].to_owned())
+                } else {
+                    None
+                };
+                Some((
+                    size,
+                    header_size,
+                    Message {
+                        subject: tokens[0].to_owned(),
+                        sid: tokens[1].to_owned(),
+                        reply_to,
+                        ..Default::default()
+                    },
+                ))
+            }
+        ) >> body: take!(item.0)
+            >> tag!("\r\n")
+            >> ({
+                let (headers, payload) = split_headers(body, item.1);
+                Message {
+                    headers,
+                    payload,
+                    ..item.2
+                }
+            })
+    )
+);
+
+//HPUB <subject> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+named!(
+    header_publish<Publish>,
+    do_parse!(
+        item: map_opt!(
+            delimited!(
+                leading_ws!(take_while!(is_space)),
+                separated_list!(take_while!(is_space), text_token),
+                leading_space!(tag!("\r\n"))
+            ),
+            |tokens: Vec<String>| {
+                let token_len = tokens.len();
+                if token_len != 3 && token_len != 4 {
+                    return None;
+                }
+                let (size, header_size) = header_sizes(&tokens)?;
+                let reply_to = if token_len > 3 {
+                    Some(tokens[1].to_owned())
+                } else {
+                    None
+                };
+                Some((
+                    size,
+                    header_size,
+                    Publish {
+                        subject: tokens[0].to_owned(),
+                        reply_to,
+                        ..Default::default()
+                    },
+                ))
+            }
+        ) >> body: take!(item.0)
+            >> tag!("\r\n")
+            >> ({
+                let (headers, payload) = split_headers(body, item.1);
+                Publish {
+                    headers,
+                    payload,
+                    ..item.2
+                }
             })
     )
 );
@@ -353,10 +470,7 @@ named!(
             let token_len = tokens.len();
             let sid = tokens[0].to_owned();
             let max_msgs: Option<u32> = if token_len > 1 {
-                match tokens[1].parse() {
-                    Ok(size) => Some(size),
-                    _ => None,
-                }
+                tokens[1].parse().ok()
             } else {
                 None
             };
@@ -370,6 +484,7 @@ named!(
     pub operation<Op>,
     alt!(
           pair!(leading_ws!(tag_no_case!("MSG")), message) => { |(_, msg)| Op::MSG(msg) }
+        | pair!(leading_ws!(tag_no_case!("HMSG")), header_message) => { |(_, msg)| Op::MSG(msg) }
         | tuple!(leading_ws!(tag_no_case!("INFO")), object, leading_space!(tag!("\r\n"))) => { |(_, json_obj, _)|
             Op::INFO(ServerInfo::from(JsonValue::Object(json_obj)))}
         | tuple!(leading_ws!(tag_no_case!("CONNECT")), object, leading_space!(tag!("\r\n"))) => { |(_, json_obj, _)|
@@ -380,8 +495,75 @@ named!(
         | tuple!(leading_ws!(tag_no_case!("-ERR")), error_msg, leading_space!(tag!("\r\n"))) => { |(_, msg, _): (_, String, _)|
            Op::ERR(msg) }
         | pair!(leading_ws!(tag_no_case!("PUB")), publish) => { |(_, publish)| Op::PUB(publish) }
+        | pair!(leading_ws!(tag_no_case!("HPUB")), header_publish) => { |(_, publish)| Op::PUB(publish) }
         | pair!(leading_ws!(tag_no_case!("SUB")), subscribe) => { |(_, sub)| Op::SUB(sub) }
         | pair!(leading_ws!(tag_no_case!("UNSUB")), un_subscribe) => { |(_, un_sub)| Op::UNSUB(un_sub) }
         //Un parsed data.
     )
 );
+
+#[test]
+fn parse_message_with_headers() {
+    let input = b"HMSG\tFOO.BAR\t9\tINBOX.34\t30\t41\r\nNATS/1.0\r\nNats-Msg-Id: abc\r\n\r\nHello World\r\n";
+    match operation(&input[..]) {
+        Ok((remaining, Op::MSG(msg))) => {
+            assert!(remaining.is_empty());
+            assert_eq!(msg.subject, "FOO.BAR");
+            assert_eq!(msg.sid, "9");
+            assert_eq!(msg.reply_to, Some(String::from("INBOX.34")));
+            assert_eq!(msg.payload, b"Hello World".to_vec());
+            assert_eq!(msg.headers.unwrap().get("Nats-Msg-Id"), Some("abc"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn parse_no_responders() {
+    let input = b"HMSG _INBOX.1 2 16 16\r\nNATS/1.0 503\r\n\r\n\r\n";
+    match operation(&input[..]) {
+        Ok((_, Op::MSG(msg))) => {
+            assert!(msg.payload.is_empty());
+            assert_eq!(msg.headers.unwrap().status, Some(503));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn parse_malformed_header_messages() {
+    let inputs: [&[u8]; 6] = [
+        b"HMSG FOO.BAR 16\r\nNATS/1.0\r\n\r\n\r\n",
+        b"HMSG FOO.BAR 9 x 16\r\nNATS/1.0\r\n\r\n\r\n",
+        b"HMSG FOO.BAR 16 16\r\nNATS/1.0\r\n\r\n\r\n",
+        b"HMSG FOO.BAR 9 32 16\r\nNATS/1.0\r\n\r\n\r\n",
+        b"HPUB 16\r\nNATS/1.0\r\n\r\n\r\n",
+        b"HPUB FOO.BAR 16 -1\r\nNATS/1.0\r\n\r\n\r\n",
+    ];
+    for input in inputs.iter() {
+        match operation(input) {
+            Err(nom::Err::Error(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn parse_permissions_violation() {
+    let input = b"-ERR 'Permissions Violation for Publish to \"foo.bar\"'\r\n";
+    match operation(&input[..]) {
+        Ok((_, Op::ERR(msg))) => {
+            assert_eq!(msg, "Permissions Violation for Publish to \"foo.bar\"");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn parse_lame_duck_info() {
+    let input = b"INFO {\"server_id\":\"ABC\",\"ldm\":true}\r\n";
+    match operation(&input[..]) {
+        Ok((_, Op::INFO(server_info))) => assert!(server_info.lame_duck_mode),
+        other => panic!("unexpected {:?}", other),
+    }
+}
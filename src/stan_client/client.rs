@@ -1,5 +1,5 @@
 use crate::error::RatsioError;
-use crate::nats_client::{ClosableMessage, NatsClient};
+use crate::nats_client::{ClosableMessage, NatsClient, DEFAULT_INBOX_PREFIX};
 use crate::nuid::NUID;
 use crate::protocol;
 use crate::stan_client::{
@@ -8,7 +8,6 @@ use crate::stan_client::{
 };
 use futures::{Stream, StreamExt};
 use nom::lib::std::collections::HashMap;
-use pin_project::pin_project;
 use prost::Message;
 use sha2::{Digest, Sha256};
 use std::pin::Pin;
@@ -24,12 +23,16 @@ impl From<protocol::ConnectResponse> for ClientInfo {
             sub_requests: protocol.sub_requests.clone(),
             unsub_requests: protocol.unsub_requests.clone(),
             close_requests: protocol.close_requests,
+            sub_close_requests: protocol.sub_close_requests,
         }
     }
 }
 
 impl StanClient {
-    pub async fn from_options(options: StanOptions) -> Result<Arc<Self>, RatsioError> {
+    pub async fn from_options(mut options: StanOptions) -> Result<Arc<Self>, RatsioError> {
+        if let Some(inbox_prefix) = options.inbox_prefix.as_ref() {
+            options.nats_options.inbox_prefix = inbox_prefix.clone();
+        }
         let id_generator = Arc::new(RwLock::new({
             let mut id_gen = NUID::new();
             id_gen.randomize_prefix();
@@ -38,7 +41,7 @@ impl StanClient {
 
         let conn_id = id_generator.write().await.next();
         debug!("Connection id => {}", &conn_id);
-        let heartbeat_inbox: String = format!("_HB.{}", id_generator.write().await.next());
+        let heartbeat_inbox = Self::inbox(&options, "_HB", id_generator.write().await.next());
         let discover_subject: String =
             format!("{}.{}", DEFAULT_DISCOVER_PREFIX, options.cluster_id);
         let client_id = options.client_id.clone();
@@ -106,6 +109,18 @@ impl StanClient {
         Ok(stan_client)
     }
 
+    // Inbox subject under the configured inbox_prefix, the one of the NATS client
+    // when only that one was changed, or the given default prefix.
+    fn inbox(options: &StanOptions, default_prefix: &str, id: String) -> String {
+        let nats_prefix = options.nats_options.inbox_prefix.as_str();
+        let prefix = match options.inbox_prefix.as_ref() {
+            Some(prefix) => prefix.as_str(),
+            None if nats_prefix != DEFAULT_INBOX_PREFIX => nats_prefix,
+            None => default_prefix,
+        };
+        format!("{}.{}", prefix.trim_end_matches('.'), id)
+    }
+
     async fn process_heartbeats(
         nats_client: Arc<NatsClient>,
         id_generator: Arc<RwLock<NUID>>,
@@ -154,7 +169,7 @@ impl StanClient {
         subject: T,
         queue_group: Option<T>,
         durable_name: Option<T>,
-    ) -> Result<(StanSid, impl Stream<Item = StanMessage> + Send + Sync), RatsioError>
+    ) -> Result<(StanSid, StanSubscription), RatsioError>
     where
         T: ToString,
     {
@@ -177,7 +192,7 @@ impl StanClient {
         subject: T,
         queue_group: Option<T>,
         durable_name: Option<T>,
-    ) -> Result<(StanSid, impl Stream<Item = StanMessage> + Send + Sync), RatsioError>
+    ) -> Result<(StanSid, StanSubscription), RatsioError>
     where
         T: ToString,
     {
@@ -195,6 +210,7 @@ impl StanClient {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn subscribe_with_all<T>(
         &self,
         subject: T,
@@ -206,7 +222,7 @@ impl StanClient {
         start_sequence: u64,
         start_time_delta: Option<i32>,
         manual_acks: bool,
-    ) -> Result<(StanSid, impl Stream<Item = StanMessage> + Send + Sync), RatsioError>
+    ) -> Result<(StanSid, StanSubscription), RatsioError>
     where
         T: ToString,
     {
@@ -227,7 +243,7 @@ impl StanClient {
     pub async fn subscribe_with<T>(
         &self,
         stan_subscribe: T,
-    ) -> Result<(StanSid, impl Stream<Item = StanMessage> + Send + Sync), RatsioError>
+    ) -> Result<(StanSid, StanSubscription), RatsioError>
     where
         T: Into<StanSubscribe>,
     {
@@ -246,6 +262,7 @@ impl StanClient {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn subscribe_inner(
         &self,
         subject: String,
@@ -257,9 +274,13 @@ impl StanClient {
         start_sequence: u64,
         start_time_delta: Option<i32>,
         manual_acks: bool,
-    ) -> Result<(StanSid, impl Stream<Item = StanMessage> + Send + Sync), RatsioError> {
+    ) -> Result<(StanSid, StanSubscription), RatsioError> {
         let client_info = self.client_info.read().await.clone();
-        let inbox: String = format!("_SUB.{}", self.id_generator.write().await.next());
+        let inbox = Self::inbox(
+            &self.options,
+            "_SUB",
+            self.id_generator.write().await.next(),
+        );
 
         let sub_request = protocol::SubscriptionRequest {
             client_id: self.client_id.clone(),
@@ -311,12 +332,15 @@ impl StanClient {
             });
 
             Ok((
-                stan_sid,
-                StanClosableReceiver {
+                stan_sid.clone(),
+                StanSubscription {
+                    sid: stan_sid,
+                    subject,
                     receiver,
                     ack_inbox,
                     manual_acks,
                     stan_client: self.get_self_reference().await,
+                    active: true,
                 },
             ))
         } else {
@@ -415,10 +439,25 @@ impl StanClient {
     }
 
     pub async fn un_subscribe(&self, stan_sid: &StanSid) -> Result<(), RatsioError> {
+        self.remove_subscription(stan_sid, false).await
+    }
+
+    // Unsubscribe removes a durable subscription from the server,
+    // closing keeps it to be resumed later.
+    async fn remove_subscription(
+        &self,
+        stan_sid: &StanSid,
+        close: bool,
+    ) -> Result<(), RatsioError> {
         let client_info = self.client_info.read().await;
         let mut subscriptions = self.subscriptions.write().await;
 
         if let Some(subscription) = subscriptions.remove(&(stan_sid.0).0) {
+            let requests = if close && subscription.durable_name.is_some() {
+                client_info.sub_close_requests.clone()
+            } else {
+                client_info.unsub_requests.clone()
+            };
             let unsub_msg = protocol::UnsubscribeRequest {
                 client_id: self.client_id.clone(),
                 subject: subscription.subject.clone(),
@@ -429,10 +468,13 @@ impl StanClient {
             let mut unsub_req_buf: Vec<u8> = Vec::with_capacity(64);
             unsub_msg.encode(&mut unsub_req_buf).unwrap();
 
-            let _ = self
-                .nats_client
-                .publish(client_info.unsub_requests.clone(), unsub_req_buf.as_slice())
-                .await;
+            // Servers without subscription close support only get the NATS unsubscription
+            if !requests.is_empty() {
+                let _ = self
+                    .nats_client
+                    .publish(requests, unsub_req_buf.as_slice())
+                    .await;
+            }
             let _ = subscription.sender.send(ClosableMessage::Close);
             self.nats_client.un_subscribe(&stan_sid.0).await
         } else {
@@ -447,7 +489,7 @@ impl StanClient {
         let close_request = protocol::CloseRequest { client_id };
         let mut close_req_buf: Vec<u8> = Vec::with_capacity(64);
         close_request.encode(&mut close_req_buf).unwrap();
-        let _ = nats_client
+        nats_client
             .publish(client_info.close_requests.clone(), &close_req_buf[..])
             .await?;
         *self.self_reference.write().await = None;
@@ -455,20 +497,63 @@ impl StanClient {
     }
 }
 
-#[pin_project]
-struct StanClosableReceiver {
-    #[pin]
+/// Stream of the messages of a STAN subscription.
+/// The subscription is closed when it is dropped, durable subscriptions
+/// are kept on the server and can be resumed with the same durable name.
+pub struct StanSubscription {
+    sid: StanSid,
+    subject: String,
     receiver: UnboundedReceiver<ClosableMessage>,
     ack_inbox: String,
     manual_acks: bool,
     stan_client: Arc<StanClient>,
+    active: bool,
+}
+
+impl StanSubscription {
+    pub fn sid(&self) -> &StanSid {
+        &self.sid
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Unsubscribe, also removing a durable subscription from the server.
+    pub async fn unsubscribe(mut self) -> Result<(), RatsioError> {
+        self.active = false;
+        self.stan_client.un_subscribe(&self.sid).await
+    }
+}
+
+impl Drop for StanSubscription {
+    fn drop(&mut self) {
+        if self.active {
+            let stan_client = self.stan_client.clone();
+            let sid = self.sid.clone();
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    let _ = stan_client.remove_subscription(&sid, true).await;
+                });
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for StanSubscription {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("StanSubscription")
+            .field("sid", &self.sid)
+            .field("subject", &self.subject)
+            .finish()
+    }
 }
 
-impl Stream for StanClosableReceiver {
+impl Stream for StanSubscription {
     type Item = StanMessage;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut this = self.project();
+        let this = self.get_mut();
         let ack_inbox = this.ack_inbox.clone();
         let manual_acks = this.manual_acks;
         let stan_client = this.stan_client.clone();
@@ -479,7 +564,7 @@ impl Stream for StanClosableReceiver {
                 let sequence = msg.sequence;
                 let ack_ack_inbox = this.ack_inbox.clone();
                 let ack_subject = subject;
-                let ack_handler = if !*manual_acks {
+                let ack_handler = if !manual_acks {
                     Some(AckHandler(Box::new(move || {
                         let ack_inbox2 = ack_ack_inbox.clone();
                         let subject2 = ack_subject.clone();
@@ -516,3 +601,23 @@ impl Stream for StanClosableReceiver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inbox_prefix_fallback() {
+        let mut options = StanOptions::default();
+        assert_eq!(StanClient::inbox(&options, "_HB", "1".into()), "_HB.1");
+
+        options.nats_options.inbox_prefix = "_INBOX.app.".into();
+        assert_eq!(
+            StanClient::inbox(&options, "_HB", "1".into()),
+            "_INBOX.app.1"
+        );
+
+        options.inbox_prefix = Some("_STAN".into());
+        assert_eq!(StanClient::inbox(&options, "_SUB", "1".into()), "_STAN.1");
+    }
+}
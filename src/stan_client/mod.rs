@@ -9,6 +9,8 @@ use tokio::sync::mpsc::UnboundedSender;
 
 pub mod client;
 
+pub use client::StanSubscription;
+
 // DefaultConnectWait is the default timeout used for the connect operation
 //const DEFAULT_CONNECT_WAIT: u64 = 2 * 60000;
 // DefaultDiscoverPrefix is the prefix subject used to connect to the NATS Streaming server
@@ -41,6 +43,10 @@ pub struct StanOptions {
 
     pub discover_prefix: String,
     pub ack_prefix: String,
+    /// Prefix of the heartbeat, subscription and request inboxes, overrides
+    /// `nats_options.inbox_prefix`. When unset, a changed `nats_options.inbox_prefix`
+    /// is used, otherwise the `_HB` and `_SUB` inboxes.
+    pub inbox_prefix: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,11 +94,12 @@ impl Default for StanOptions {
 
             discover_prefix: DEFAULT_DISCOVER_PREFIX.into(),
             ack_prefix: DEFAULT_ACK_PREFIX.into(),
+            inbox_prefix: None,
         }
     }
 }
 
-pub(crate) struct AckHandler(Box<dyn Fn() -> () + Send + Sync>);
+pub(crate) struct AckHandler(Box<dyn Fn() + Send + Sync>);
 
 impl Drop for AckHandler {
     fn drop(&mut self) {
@@ -263,4 +270,5 @@ pub struct ClientInfo {
     sub_requests: String,
     unsub_requests: String,
     close_requests: String,
+    sub_close_requests: String,
 }
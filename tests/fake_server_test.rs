@@ -0,0 +1,1122 @@
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+use ratsio::error::RatsioError;
+use ratsio::nats_client::{
+    ClientEvent, DnsResolver, NatsClient, NatsClientOptions, NatsClientState, ReconnectPolicy,
+    RequestManyOptions, Resolver, Subscription,
+};
+use ratsio::protocol;
+use ratsio::stan_client::{StanClient, StanOptions};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+// Minimal NATS server speaking just enough of the protocol to drive a `NatsClient`.
+// Every op received is recorded with the number of the connection it came on.
+struct FakeServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    auth_token: Option<String>,
+    accepting: AtomicBool,
+    pong: AtomicBool,
+    info_delay: Mutex<Option<(usize, Duration)>>,
+    received: Mutex<Vec<(usize, String)>>,
+    connections: Mutex<Vec<Connection>>,
+}
+
+struct Connection {
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+    task: JoinHandle<()>,
+}
+
+impl FakeServer {
+    async fn start() -> FakeServer {
+        FakeServer::with_auth_token(None).await
+    }
+
+    async fn with_auth_token(auth_token: Option<&str>) -> FakeServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Arc::new(Shared {
+            auth_token: auth_token.map(String::from),
+            accepting: AtomicBool::new(true),
+            pong: AtomicBool::new(true),
+            ..Default::default()
+        });
+        let accept_shared = shared.clone();
+        tokio::spawn(async move {
+            let mut count = 0;
+            while let Ok((socket, _)) = listener.accept().await {
+                if !accept_shared.accepting.load(Ordering::SeqCst) {
+                    continue;
+                }
+                count += 1;
+                let (reader, writer) = socket.into_split();
+                let writer = Arc::new(tokio::sync::Mutex::new(writer));
+                let task =
+                    tokio::spawn(serve(accept_shared.clone(), count, reader, writer.clone()));
+                let mut connections = accept_shared.connections.lock().unwrap();
+                connections.push(Connection { writer, task });
+            }
+        });
+        FakeServer { addr, shared }
+    }
+
+    fn options(&self) -> NatsClientOptions {
+        NatsClientOptions {
+            cluster_uris: vec![format!("nats://{}", self.addr)].into(),
+            // No heartbeat PING in the way of the ones under test
+            ping_interval: 60,
+            reconnect_policy: ReconnectPolicy {
+                initial_delay: Duration::from_millis(20),
+                max_delay: Duration::from_millis(100),
+                jitter: Duration::from_millis(0),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    // Close the open connections, refusing new ones until `accept` is called
+    fn disconnect(&self) {
+        self.shared.accepting.store(false, Ordering::SeqCst);
+        for connection in self.shared.connections.lock().unwrap().drain(..) {
+            connection.task.abort();
+        }
+    }
+
+    fn accept(&self) {
+        self.shared.accepting.store(true, Ordering::SeqCst);
+    }
+
+    // Wait before sending INFO on the given connection
+    fn delay_info(&self, connection: usize, delay: Duration) {
+        *self.shared.info_delay.lock().unwrap() = Some((connection, delay));
+    }
+
+    // Whether PINGs are answered, PONGs can still be sent with `send`
+    fn set_pong(&self, pong: bool) {
+        self.shared.pong.store(pong, Ordering::SeqCst);
+    }
+
+    // Write raw protocol to every open connection
+    async fn send(&self, protocol: impl AsRef<[u8]>) {
+        let mut bytes = protocol.as_ref().to_vec();
+        bytes.extend_from_slice(b"\r\n");
+        let writers: Vec<_> = {
+            let connections = self.shared.connections.lock().unwrap();
+            connections.iter().map(|conn| conn.writer.clone()).collect()
+        };
+        for writer in writers {
+            let _ = writer.lock().await.write_all(&bytes).await;
+        }
+    }
+
+    // Ops received on the given connection, numbered from 1
+    fn received_on(&self, connection: usize) -> Vec<String> {
+        let received = self.shared.received.lock().unwrap();
+        received
+            .iter()
+            .filter(|(conn, _)| *conn == connection)
+            .map(|(_, op)| op.clone())
+            .collect()
+    }
+
+    // Wait until the ops received on the connection match the predicate
+    async fn wait_for<F>(&self, connection: usize, predicate: F) -> Vec<String>
+    where
+        F: Fn(&[String]) -> bool,
+    {
+        for _ in 0..500 {
+            let received = self.received_on(connection);
+            if predicate(&received) {
+                return received;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!(
+            "Unexpected ops on connection {} => {:?}",
+            connection,
+            self.received_on(connection)
+        );
+    }
+}
+
+async fn serve(
+    shared: Arc<Shared>,
+    connection: usize,
+    reader: tokio::net::tcp::OwnedReadHalf,
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+) {
+    let write = |line: String| {
+        let writer = writer.clone();
+        async move {
+            let _ = writer.lock().await.write_all(line.as_bytes()).await;
+        }
+    };
+    let info = format!(
+        r#"{{"server_id":"fake","version":"2.2.0","go":"go1","host":"127.0.0.1","port":4222,"max_payload":1048576,"proto":1,"headers":true,"auth_required":{}}}"#,
+        shared.auth_token.is_some()
+    );
+    let info_delay = *shared.info_delay.lock().unwrap();
+    if let Some((_, delay)) = info_delay.filter(|(conn, _)| *conn == connection) {
+        tokio::time::sleep(delay).await;
+    }
+    write(format!("INFO {}\r\n", info)).await;
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let op = match words[0].to_uppercase().as_str() {
+            "CONNECT" => {
+                if let Some(token) = shared.auth_token.as_ref() {
+                    if !line.contains(&format!(r#""auth_token": "{}""#, token)) {
+                        write("-ERR 'Authorization Violation'\r\n".into()).await;
+                        return;
+                    }
+                }
+                "CONNECT".to_string()
+            }
+            "PING" => {
+                if shared.pong.load(Ordering::SeqCst) {
+                    write("PONG\r\n".into()).await;
+                }
+                "PING".to_string()
+            }
+            "PUB" | "HPUB" => {
+                let size: usize = words[words.len() - 1].parse().unwrap();
+                let mut payload = vec![0; size + 2];
+                if reader.read_exact(&mut payload).await.is_err() {
+                    return;
+                }
+                payload.truncate(size);
+                // The subject, the reply subject if any, then the payload
+                let header_fields = if words[0] == "HPUB" { 2 } else { 1 };
+                let mut op = words[..words.len() - header_fields].join(" ");
+                op.push(' ');
+                op.push_str(&String::from_utf8_lossy(&payload));
+                op
+            }
+            _ => words.join(" "),
+        };
+        shared.received.lock().unwrap().push((connection, op));
+    }
+}
+
+async fn wait_for_state(client: &NatsClient, predicate: impl Fn(&NatsClientState) -> bool) {
+    for _ in 0..500 {
+        if predicate(&client.state().await) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Unexpected client state {:?}", client.state().await);
+}
+
+fn published(received: &[String]) -> Vec<String> {
+    received
+        .iter()
+        .filter(|op| op.starts_with("PUB "))
+        .cloned()
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn close_while_publishing() {
+    let server = FakeServer::start().await;
+    for _ in 0..5 {
+        let client = NatsClient::new(server.options()).await.unwrap();
+        let publishers: Vec<_> = (0..16)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    while client.publish("foo", b"bar").await.is_ok() {
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tokio::time::timeout(Duration::from_secs(5), client.close())
+            .await
+            .expect("close hangs with concurrent publishes")
+            .unwrap();
+        for publisher in publishers {
+            tokio::time::timeout(Duration::from_secs(5), publisher)
+                .await
+                .expect("publish hangs after close")
+                .unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn buffer_publishes_while_reconnecting() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (_sid, _subscription) = client.subscribe("foo").await.unwrap();
+    server
+        .wait_for(1, |ops| ops.iter().any(|op| op.starts_with("SUB foo")))
+        .await;
+
+    server.disconnect();
+    wait_for_state(&client, |state| *state != NatsClientState::Connected).await;
+    for payload in ["1", "2", "3"] {
+        client.publish("foo", payload.as_bytes()).await.unwrap();
+    }
+    server.accept();
+    wait_for_state(&client, |state| *state == NatsClientState::Connected).await;
+    client.publish("foo", b"4").await.unwrap();
+
+    let received = server.wait_for(2, |ops| published(ops).len() == 4).await;
+    assert_eq!(
+        published(&received),
+        vec!["PUB foo 1", "PUB foo 2", "PUB foo 3", "PUB foo 4"]
+    );
+    let subscribed = received.iter().position(|op| op.starts_with("SUB foo"));
+    let first_publish = received.iter().position(|op| op.starts_with("PUB "));
+    assert!(subscribed < first_publish, "{:?}", received);
+    client.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn close_while_reconnecting() {
+    let server = FakeServer::start().await;
+    server.delay_info(2, Duration::from_millis(800));
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (_sid, _subscription) = client.subscribe("foo").await.unwrap();
+    server.disconnect();
+    server.accept();
+    wait_for_state(&client, |state| *state == NatsClientState::Reconnecting).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tokio::time::timeout(Duration::from_secs(5), client.close())
+        .await
+        .expect("close hangs while reconnecting")
+        .unwrap();
+    assert_eq!(client.state().await, NatsClientState::Shutdown);
+}
+
+#[tokio::test]
+async fn reconnect_buffer_full() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(NatsClientOptions {
+        reconnect_buffer_size: 4,
+        ..server.options()
+    })
+    .await
+    .unwrap();
+    server.disconnect();
+    wait_for_state(&client, |state| *state != NatsClientState::Connected).await;
+    client.publish("foo", b"1234").await.unwrap();
+    assert!(matches!(
+        client.publish("foo", b"5").await,
+        Err(RatsioError::ReconnectBufferFull)
+    ));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn publish_after_close() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    client.close().await.unwrap();
+    assert!(matches!(
+        client.publish("foo", b"bar").await,
+        Err(RatsioError::ConnectionClosed)
+    ));
+}
+
+fn count(received: &[String], op: &str) -> usize {
+    received.iter().filter(|received| *received == op).count()
+}
+
+#[tokio::test]
+async fn flush_matches_pongs_in_order() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    client.flush(Duration::from_secs(1)).await.unwrap();
+    assert!(client.rtt().await.unwrap() < Duration::from_secs(1));
+
+    server.set_pong(false);
+    let pings = count(&server.received_on(1), "PING");
+    let flush = |client: Arc<NatsClient>| {
+        tokio::spawn(async move { client.flush(Duration::from_secs(5)).await })
+    };
+    let mut first = flush(client.clone());
+    server
+        .wait_for(1, |ops| count(ops, "PING") == pings + 1)
+        .await;
+    let mut second = flush(client.clone());
+    server
+        .wait_for(1, |ops| count(ops, "PING") == pings + 2)
+        .await;
+
+    server.send("PONG").await;
+    tokio::time::timeout(Duration::from_secs(1), &mut first)
+        .await
+        .expect("first flush not matched to the first PONG")
+        .unwrap()
+        .unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), &mut second)
+            .await
+            .is_err()
+    );
+    server.send("PONG").await;
+    tokio::time::timeout(Duration::from_secs(1), second)
+        .await
+        .expect("second flush not matched to the second PONG")
+        .unwrap()
+        .unwrap();
+
+    assert!(matches!(
+        client.flush(Duration::from_millis(100)).await,
+        Err(RatsioError::FlushTimeout)
+    ));
+    client.close().await.unwrap();
+}
+
+// Subscribe and return the sid the server got, to send messages to
+async fn subscribe(
+    server: &FakeServer,
+    client: &NatsClient,
+    subject: &str,
+) -> (
+    ratsio::nats_client::NatsSid,
+    JoinHandle<Vec<String>>,
+    String,
+) {
+    let (sid, subscription) = client.subscribe(subject).await.unwrap();
+    let server_sid = server_sid(server, 1, subject).await;
+    (sid, collect(subscription), server_sid)
+}
+
+// Wait for the SUB of the subject on the connection and return its sid
+async fn server_sid(server: &FakeServer, connection: usize, subject: &str) -> String {
+    let prefix = format!("SUB {} ", subject);
+    let received = server
+        .wait_for(connection, |ops| {
+            ops.iter().any(|op| op.starts_with(&prefix))
+        })
+        .await;
+    received
+        .iter()
+        .find_map(|op| op.strip_prefix(&prefix))
+        .unwrap()
+        .to_string()
+}
+
+// Collects the payloads until the stream ends
+fn collect(mut subscription: Subscription) -> JoinHandle<Vec<String>> {
+    tokio::spawn(async move {
+        let mut messages = Vec::new();
+        while let Some(message) = subscription.next().await {
+            messages.push(String::from_utf8(message.payload).unwrap());
+        }
+        messages
+    })
+}
+
+#[tokio::test]
+async fn drain_subscription_delivers_messages_sent_before_pong() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (sid, messages, server_sid) = subscribe(&server, &client, "foo").await;
+    server.send(&format!("MSG foo {} 1\r\n1", server_sid)).await;
+
+    server.set_pong(false);
+    let pings = count(&server.received_on(1), "PING");
+    let drain = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .drain_subscription(&sid, Duration::from_secs(5))
+                .await
+        })
+    };
+    let unsub = format!("UNSUB {}", server_sid);
+    server
+        .wait_for(1, |ops| {
+            ops.contains(&unsub) && count(ops, "PING") == pings + 1
+        })
+        .await;
+    server
+        .send(&format!("MSG foo {} 1\r\n2\r\nPONG", server_sid))
+        .await;
+
+    tokio::time::timeout(Duration::from_secs(1), drain)
+        .await
+        .expect("drain does not end")
+        .unwrap()
+        .unwrap();
+    let messages = tokio::time::timeout(Duration::from_secs(1), messages)
+        .await
+        .expect("the drained subscription does not end")
+        .unwrap();
+    assert_eq!(messages, vec!["1", "2"]);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn drain_subscription_timeout() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (sid, _messages, _) = subscribe(&server, &client, "foo").await;
+    server.set_pong(false);
+    assert!(matches!(
+        client
+            .drain_subscription(&sid, Duration::from_millis(100))
+            .await,
+        Err(RatsioError::DrainTimeout)
+    ));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn drain_client() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (_sid, messages, server_sid) = subscribe(&server, &client, "foo").await;
+    server.send(&format!("MSG foo {} 1\r\n1", server_sid)).await;
+    client.publish("bar", b"before").await.unwrap();
+
+    client.drain(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(client.state().await, NatsClientState::Shutdown);
+    let received = server.received_on(1);
+    assert!(received.contains(&format!("UNSUB {}", server_sid)));
+    assert!(received.contains(&"PUB bar before".to_string()));
+    assert_eq!(messages.await.unwrap(), vec!["1"]);
+    assert!(matches!(
+        client.publish("bar", b"after").await,
+        Err(RatsioError::ConnectionClosed)
+    ));
+}
+
+#[tokio::test]
+async fn subscribe_with_limit() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (sid, subscription) = client.subscribe_with_limit("foo", 3).await.unwrap();
+    let messages = collect(subscription);
+    let server_sid = server_sid(&server, 1, "foo").await;
+    let unsub = format!("UNSUB {} 3", server_sid);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+
+    server
+        .send(&format!(
+            "MSG foo {0} 1\r\n1\r\nMSG foo {0} 1\r\n2",
+            server_sid
+        ))
+        .await;
+    client.flush(Duration::from_secs(1)).await.unwrap();
+    assert!(client.pending_counts(&sid).await.is_ok());
+
+    // Only the messages left are asked for once reconnected
+    server.disconnect();
+    server.accept();
+    let resubscribe = format!("SUB foo {}", server_sid);
+    let unsub = format!("UNSUB {} 1", server_sid);
+    server
+        .wait_for(2, |ops| ops.contains(&resubscribe) && ops.contains(&unsub))
+        .await;
+    wait_for_state(&client, |state| *state == NatsClientState::Connected).await;
+
+    server.send(&format!("MSG foo {} 1\r\n3", server_sid)).await;
+    let messages = tokio::time::timeout(Duration::from_secs(1), messages)
+        .await
+        .expect("the subscription does not end after the last message")
+        .unwrap();
+    assert_eq!(messages, vec!["1", "2", "3"]);
+    assert!(matches!(
+        client.pending_counts(&sid).await,
+        Err(RatsioError::StreamClosed(_))
+    ));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn auto_unsubscribe_after_max_msgs() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (sid, messages, server_sid) = subscribe(&server, &client, "foo").await;
+    server
+        .send(&format!(
+            "MSG foo {0} 1\r\n1\r\nMSG foo {0} 1\r\n2",
+            server_sid
+        ))
+        .await;
+    client.flush(Duration::from_secs(1)).await.unwrap();
+
+    assert!(matches!(
+        client.auto_unsubscribe(&sid, 2).await,
+        Err(RatsioError::SubscriptionReachedMaxMsgs(2))
+    ));
+    let unsub = format!("UNSUB {}", server_sid);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+    let messages = tokio::time::timeout(Duration::from_secs(1), messages)
+        .await
+        .expect("the subscription does not end")
+        .unwrap();
+    assert_eq!(messages, vec!["1", "2"]);
+    assert!(matches!(
+        client.pending_counts(&sid).await,
+        Err(RatsioError::StreamClosed(_))
+    ));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn handshake_waits_for_pong() {
+    let server = FakeServer::with_auth_token(Some("s3cr3t")).await;
+    let client = NatsClient::new(NatsClientOptions {
+        auth_token: "s3cr3t".into(),
+        ..server.options()
+    })
+    .await
+    .unwrap();
+    assert_eq!(client.state().await, NatsClientState::Connected);
+    assert_eq!(server.received_on(1)[..2], ["CONNECT", "PING"]);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn handshake_authorization_violation() {
+    let server = FakeServer::with_auth_token(Some("s3cr3t")).await;
+    let result = NatsClient::new(NatsClientOptions {
+        auth_token: "wrong".into(),
+        ..server.options()
+    })
+    .await;
+    assert!(matches!(result, Err(RatsioError::AuthorizationViolation)));
+}
+
+#[tokio::test]
+async fn handshake_timeout_without_pong() {
+    let server = FakeServer::start().await;
+    server.set_pong(false);
+    let result = NatsClient::new(NatsClientOptions {
+        handshake_timeout: Duration::from_millis(200),
+        ..server.options()
+    })
+    .await;
+    assert!(matches!(result, Err(RatsioError::HandshakeTimeout(_))));
+}
+
+// Resolves every host to the fake server, or fails without an address
+struct CountingResolver {
+    addr: Option<SocketAddr>,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl Resolver for CountingResolver {
+    fn resolve(&self, _host: &str, _port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        let resolved = match self.addr {
+            Some(addr) => Ok(vec![addr]),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "unknown host")),
+        };
+        Box::pin(async move { resolved })
+    }
+}
+
+#[tokio::test]
+async fn resolve_on_every_reconnect_attempt() {
+    let server = FakeServer::start().await;
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let client = NatsClient::new(NatsClientOptions {
+        cluster_uris: vec!["nats://nats.example.com".to_string()].into(),
+        resolver: DnsResolver::new(CountingResolver {
+            addr: Some(server.addr),
+            lookups: lookups.clone(),
+        }),
+        ..server.options()
+    })
+    .await
+    .unwrap();
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+    server.disconnect();
+    wait_for_state(&client, |state| *state != NatsClientState::Connected).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let attempts = lookups.load(Ordering::SeqCst);
+    assert!(attempts > 3, "{} lookups", attempts);
+
+    server.accept();
+    wait_for_state(&client, |state| *state == NatsClientState::Connected).await;
+    assert!(lookups.load(Ordering::SeqCst) > attempts);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn report_resolution_errors() {
+    let server = FakeServer::start().await;
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let result = NatsClient::new(NatsClientOptions {
+        resolver: DnsResolver::new(CountingResolver {
+            addr: None,
+            lookups: lookups.clone(),
+        }),
+        ..server.options()
+    })
+    .await;
+    match result {
+        Err(RatsioError::ResolveError(url, err)) => {
+            assert_eq!(url, format!("nats://{}", server.addr));
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        }
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+}
+
+// Reply subject and payload of the requests published to the subject
+fn requests(received: &[String], subject: &str) -> Vec<(String, String)> {
+    received
+        .iter()
+        .filter_map(|op| match op.splitn(4, ' ').collect::<Vec<_>>()[..] {
+            ["PUB", to, reply_to, payload] if to == subject => {
+                Some((reply_to.to_string(), payload.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn wait_for_requests(
+    server: &FakeServer,
+    subject: &str,
+    count: usize,
+) -> Vec<(String, String)> {
+    let received = server
+        .wait_for(1, |ops| requests(ops, subject).len() >= count)
+        .await;
+    requests(&received, subject)
+}
+
+// Sid of the subscription receiving the reply subject, the shared `<inbox>.*` one
+async fn inbox_sid(server: &FakeServer, reply_to: &str) -> String {
+    let inbox = &reply_to[..=reply_to.rfind('.').unwrap()];
+    server_sid(server, 1, &format!("{}*", inbox)).await
+}
+
+fn spawn_request(
+    client: &Arc<NatsClient>,
+    payload: &str,
+) -> JoinHandle<Result<String, RatsioError>> {
+    let client = client.clone();
+    let payload = payload.to_string();
+    tokio::spawn(async move {
+        let reply = client
+            .request_timeout("svc", payload.as_bytes(), Duration::from_secs(5))
+            .await?;
+        Ok(String::from_utf8(reply.payload).unwrap())
+    })
+}
+
+#[tokio::test]
+async fn requests_share_one_inbox_subscription() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let first = spawn_request(&client, "a");
+    let second = spawn_request(&client, "b");
+    let requests = wait_for_requests(&server, "svc", 2).await;
+    let reply_to = |payload: &str| {
+        let (reply_to, _) = requests.iter().find(|(_, sent)| sent == payload).unwrap();
+        reply_to.clone()
+    };
+    let (reply_a, reply_b) = (reply_to("a"), reply_to("b"));
+    assert_ne!(reply_a, reply_b);
+    let sid = inbox_sid(&server, &reply_a).await;
+    assert_eq!(inbox_sid(&server, &reply_b).await, sid);
+    let inboxes = server
+        .received_on(1)
+        .iter()
+        .filter(|op| op.starts_with("SUB _INBOX."))
+        .count();
+    assert_eq!(inboxes, 1);
+
+    // Routed by the token ending the reply subject, whatever the order
+    server
+        .send(&format!(
+            "MSG {} {2} 1\r\nB\r\nMSG {} {2} 1\r\nA",
+            reply_b, reply_a, sid
+        ))
+        .await;
+    assert_eq!(first.await.unwrap().unwrap(), "A");
+    assert_eq!(second.await.unwrap().unwrap(), "B");
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn request_no_responders() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let request = spawn_request(&client, "a");
+    let (reply_to, _) = wait_for_requests(&server, "svc", 1).await.remove(0);
+    let sid = inbox_sid(&server, &reply_to).await;
+    server
+        .send(&format!(
+            "HMSG {} {} 16 16\r\nNATS/1.0 503\r\n\r\n",
+            reply_to, sid
+        ))
+        .await;
+    assert!(matches!(
+        request.await.unwrap(),
+        Err(RatsioError::NoResponders)
+    ));
+    assert!(matches!(
+        client
+            .request_timeout("svc", b"b", Duration::from_millis(100))
+            .await,
+        Err(RatsioError::RequestTimeout)
+    ));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn old_request_style() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(NatsClientOptions {
+        old_request_style: true,
+        ..server.options()
+    })
+    .await
+    .unwrap();
+    let request = spawn_request(&client, "a");
+    let (reply_to, _) = wait_for_requests(&server, "svc", 1).await.remove(0);
+    let sid = server_sid(&server, 1, &reply_to).await;
+    server
+        .send(&format!("MSG {} {} 2\r\nok", reply_to, sid))
+        .await;
+    assert_eq!(request.await.unwrap().unwrap(), "ok");
+    let unsub = format!("UNSUB {}", sid);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+
+    // Unsubscribed on timeout
+    assert!(matches!(
+        client
+            .request_timeout("svc", b"b", Duration::from_millis(100))
+            .await,
+        Err(RatsioError::RequestTimeout)
+    ));
+    let (reply_to, _) = wait_for_requests(&server, "svc", 2).await.remove(1);
+    let unsub = format!("UNSUB {}", server_sid(&server, 1, &reply_to).await);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+
+    // And when the request is dropped
+    let request = spawn_request(&client, "c");
+    let (reply_to, _) = wait_for_requests(&server, "svc", 3).await.remove(2);
+    request.abort();
+    let unsub = format!("UNSUB {}", server_sid(&server, 1, &reply_to).await);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+
+    let received = server.received_on(1);
+    assert!(!received
+        .iter()
+        .any(|op| op.starts_with("SUB ") && op.contains(".* ")));
+    client.close().await.unwrap();
+}
+
+// Send the replies to a new request_many and return the ones received until the stream ended
+async fn request_many(
+    server: &FakeServer,
+    client: &Arc<NatsClient>,
+    options: RequestManyOptions,
+    replies: &[&str],
+) -> (Vec<String>, Duration) {
+    let count = requests(&server.received_on(1), "svc").len();
+    let started = Instant::now();
+    let request = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let replies = client
+                .request_many_with_options("svc", b"", options)
+                .await
+                .unwrap();
+            replies
+                .map(|reply| String::from_utf8(reply.payload).unwrap())
+                .collect::<Vec<_>>()
+                .await
+        })
+    };
+    let (reply_to, _) = wait_for_requests(server, "svc", count + 1)
+        .await
+        .remove(count);
+    let sid = inbox_sid(server, &reply_to).await;
+    for reply in replies {
+        server
+            .send(&format!(
+                "MSG {} {} {}\r\n{}",
+                reply_to,
+                sid,
+                reply.len(),
+                reply
+            ))
+            .await;
+    }
+    let received = tokio::time::timeout(Duration::from_secs(2), request)
+        .await
+        .expect("the replies do not end")
+        .unwrap();
+    (received, started.elapsed())
+}
+
+#[tokio::test]
+async fn request_many_end_conditions() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+
+    let options = RequestManyOptions {
+        max_messages: Some(2),
+        ..Default::default()
+    };
+    let (replies, elapsed) = request_many(&server, &client, options, &["1", "2", "3"]).await;
+    assert_eq!(replies, vec!["1", "2"]);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+    let options = RequestManyOptions {
+        timeout: Some(Duration::from_millis(300)),
+        ..Default::default()
+    };
+    let (replies, elapsed) = request_many(&server, &client, options, &["1"]).await;
+    assert_eq!(replies, vec!["1"]);
+    assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+
+    let options = RequestManyOptions {
+        timeout: None,
+        stall_wait: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let (replies, _) = request_many(&server, &client, options, &["1", "2"]).await;
+    assert_eq!(replies, vec!["1", "2"]);
+
+    let options = RequestManyOptions {
+        sentinel: true,
+        ..Default::default()
+    };
+    let (replies, elapsed) = request_many(&server, &client, options, &["1", "", "3"]).await;
+    assert_eq!(replies, vec!["1"]);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn drop_subscription() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let (sid, subscription) = client.subscribe("foo").await.unwrap();
+    let server_sid = server_sid(&server, 1, "foo").await;
+    drop(subscription);
+
+    let unsub = format!("UNSUB {}", server_sid);
+    server.wait_for(1, |ops| ops.contains(&unsub)).await;
+    assert!(matches!(
+        client.pending_counts(&sid).await,
+        Err(RatsioError::StreamClosed(_))
+    ));
+    client.close().await.unwrap();
+}
+
+// Answer the request published to the subject with a protobuf message
+async fn reply_to_request(server: &FakeServer, subject: &str, reply: impl prost::Message) {
+    let (reply_to, _) = wait_for_requests(server, subject, 1).await.remove(0);
+    let sid = inbox_sid(server, &reply_to).await;
+    let mut payload = Vec::new();
+    reply.encode(&mut payload).unwrap();
+    let mut protocol = format!("MSG {} {} {}\r\n", reply_to, sid, payload.len()).into_bytes();
+    protocol.extend_from_slice(&payload);
+    server.send(protocol).await;
+}
+
+#[tokio::test]
+async fn drop_stan_subscription() {
+    let server = FakeServer::start().await;
+    let options = StanOptions::with_options(server.options(), "test-cluster", "test-client");
+    let stan_client = tokio::spawn(StanClient::from_options(options));
+    let connected = protocol::ConnectResponse {
+        pub_prefix: "_STAN.pub".into(),
+        sub_requests: "_STAN.sub".into(),
+        unsub_requests: "_STAN.unsub".into(),
+        close_requests: "_STAN.close".into(),
+        sub_close_requests: "_STAN.subclose".into(),
+        ..Default::default()
+    };
+    reply_to_request(&server, "_STAN.discover.test-cluster", connected).await;
+    let stan_client = stan_client.await.unwrap().unwrap();
+
+    let subscribe = {
+        let stan_client = stan_client.clone();
+        tokio::spawn(async move { stan_client.subscribe("foo", None, None).await })
+    };
+    let subscribed = protocol::SubscriptionResponse {
+        ack_inbox: "_STAN.ack".into(),
+        ..Default::default()
+    };
+    reply_to_request(&server, "_STAN.sub", subscribed).await;
+    let (_sid, subscription) = subscribe.await.unwrap().unwrap();
+    let inbox = server
+        .wait_for(1, |ops| ops.iter().any(|op| op.starts_with("SUB _SUB.")))
+        .await
+        .iter()
+        .find_map(|op| op.strip_prefix("SUB _SUB.").map(String::from))
+        .unwrap();
+    let (inbox, server_sid) = inbox.split_once(' ').unwrap();
+    drop(subscription);
+
+    // The STAN subscription is closed, then its NATS subscription
+    let unsub = format!("UNSUB {}", server_sid);
+    let received = server.wait_for(1, |ops| ops.contains(&unsub)).await;
+    let published_to = |subject: &str| -> Vec<String> {
+        let prefix = format!("PUB {} ", subject);
+        let ops = received.iter().filter_map(|op| op.strip_prefix(&prefix));
+        ops.map(String::from).collect()
+    };
+    let request = &published_to("_STAN.unsub")[0];
+    assert!(request.contains("test-client"), "{}", request);
+    assert!(request.contains(&format!("_SUB.{}", inbox)), "{}", request);
+    assert!(published_to("_STAN.subclose").is_empty());
+    stan_client.close().await.unwrap();
+}
+
+async fn next_event(events: &mut (impl Stream<Item = ClientEvent> + Unpin)) -> Option<ClientEvent> {
+    tokio::time::timeout(Duration::from_secs(2), events.next())
+        .await
+        .expect("no client event")
+}
+
+#[tokio::test]
+async fn lifecycle_events() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let mut events = client.events().await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Connected(_))
+    ));
+
+    server.disconnect();
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Disconnected(_))
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Reconnecting(1))
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Reconnecting(2))
+    ));
+    server.accept();
+    let mut attempt = 2;
+    loop {
+        match next_event(&mut events).await {
+            Some(ClientEvent::Reconnecting(next)) => {
+                assert_eq!(next, attempt + 1);
+                attempt = next;
+            }
+            Some(ClientEvent::Reconnected(_)) => break,
+            other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    client.close().await.unwrap();
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Closed)
+    ));
+    assert!(next_event(&mut events).await.is_none());
+}
+
+// Error handler keeping the errors reported
+async fn record_errors(client: &NatsClient) -> Arc<Mutex<Vec<String>>> {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let recorded = errors.clone();
+    client
+        .add_error_handler(Box::new(move |_, err| {
+            recorded.lock().unwrap().push(err.to_string())
+        }))
+        .await
+        .unwrap();
+    errors
+}
+
+#[tokio::test]
+async fn subscribe_permission_violation() {
+    let server = FakeServer::start().await;
+    let client = NatsClient::new(server.options()).await.unwrap();
+    let errors = record_errors(&client).await;
+    let (foo_sid, mut foo) = client.subscribe("foo").await.unwrap();
+    let (bar_sid, _bar) = client.subscribe("bar").await.unwrap();
+    server_sid(&server, 1, "bar").await;
+    let mut events = client.events().await;
+    next_event(&mut events).await;
+
+    server
+        .send(r#"-ERR 'Permissions Violation for Subscription to "foo"'"#)
+        .await;
+    let end = tokio::time::timeout(Duration::from_secs(1), foo.next()).await;
+    assert!(matches!(end, Ok(None)), "the subscription does not end");
+    assert!(matches!(
+        foo.take_error(),
+        Some(RatsioError::SubscribePermissionViolation(subject)) if subject == "foo"
+    ));
+    assert!(client.pending_counts(&foo_sid).await.is_err());
+    assert!(client.pending_counts(&bar_sid).await.is_ok());
+
+    match next_event(&mut events).await {
+        Some(ClientEvent::ServerError(err)) => {
+            assert!(matches!(*err, RatsioError::SubscribePermissionViolation(_)))
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert_eq!(errors.lock().unwrap().len(), 1);
+    assert_eq!(client.state().await, NatsClientState::Connected);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn give_up_after_max_attempts() {
+    let server = FakeServer::start().await;
+    let mut options = server.options();
+    options.reconnect_policy.max_attempts = Some(2);
+    let client = NatsClient::new(options).await.unwrap();
+    let errors = record_errors(&client).await;
+    let mut events = client.events().await;
+    next_event(&mut events).await;
+
+    server.disconnect();
+    wait_for_state(&client, |state| *state == NatsClientState::Shutdown).await;
+    let mut reconnecting = Vec::new();
+    loop {
+        match next_event(&mut events).await {
+            Some(ClientEvent::Reconnecting(attempt)) => reconnecting.push(attempt),
+            Some(ClientEvent::Closed) => break,
+            Some(ClientEvent::Disconnected(_)) => {}
+            other => panic!("Unexpected event {:?}", other),
+        }
+    }
+    assert_eq!(reconnecting, vec![1, 2]);
+    assert_eq!(
+        *errors.lock().unwrap(),
+        vec![RatsioError::CannotReconnectToServer.to_string()]
+    );
+    assert!(matches!(
+        client.publish("foo", b"bar").await,
+        Err(RatsioError::ConnectionClosed)
+    ));
+}
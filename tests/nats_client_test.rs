@@ -3,9 +3,6 @@ use log::info;
 use ratsio::error::RatsioError;
 use ratsio::nats_client::NatsClient;
 
-use ratsio::nuid;
-use ratsio::protocol;
-
 pub fn logger_setup() {
     use env_logger::Builder;
     use log::LevelFilter;
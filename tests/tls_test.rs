@@ -0,0 +1,132 @@
+#![cfg(any(feature = "tls", feature = "rustls"))]
+// The fixtures are a CA and a server certificate for `localhost`, valid until 2126:
+//
+// openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+//     -keyout ca-key.pem -out ca.pem -days 36500 -subj "/CN=ratsio test CA" \
+//     -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign"
+// openssl req -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+//     -keyout server-key.pem -out server.csr -subj "/CN=localhost"
+// openssl x509 -req -in server.csr -CA ca.pem -CAkey ca-key.pem -out server.pem -days 36500 \
+//     -extfile server.ext
+//
+// with server.ext holding, one per line, `subjectAltName=DNS:localhost`,
+// `basicConstraints=critical,CA:FALSE`, `keyUsage=critical,digitalSignature`
+// and `extendedKeyUsage=serverAuth`.
+use futures::future::BoxFuture;
+use ratsio::error::RatsioError;
+use ratsio::nats_client::{DnsResolver, NatsClient, NatsClientOptions, NatsClientState, Resolver};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const CA: &[u8] = include_bytes!("fixtures/ca.pem");
+const SERVER_CERT: &[u8] = include_bytes!("fixtures/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("fixtures/server-key.pem");
+
+#[cfg(feature = "rustls")]
+fn acceptor() -> tokio_rustls::TlsAcceptor {
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+    let certs = rustls_pemfile::certs(&mut &SERVER_CERT[..]).unwrap();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut &SERVER_KEY[..])
+        .unwrap()
+        .remove(0);
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certs.into_iter().map(Certificate).collect(),
+            PrivateKey(key),
+        )
+        .unwrap();
+    tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(config))
+}
+
+#[cfg(not(feature = "rustls"))]
+fn acceptor() -> tokio_native_tls::TlsAcceptor {
+    let identity = native_tls::Identity::from_pkcs8(SERVER_CERT, SERVER_KEY).unwrap();
+    tokio_native_tls::TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap())
+}
+
+// Server sending INFO in plain text with `tls_required`, then answering PINGs over TLS
+async fn tls_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let acceptor = acceptor();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let info = r#"{"server_id":"tls","max_payload":1048576,"tls_required":true}"#;
+                let info = format!("INFO {}\r\n", info);
+                socket.write_all(info.as_bytes()).await.unwrap();
+                let stream = match acceptor.accept(socket).await {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let (reader, mut writer) = tokio::io::split(stream);
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.starts_with("PING") {
+                        let _ = writer.write_all(b"PONG\r\n").await;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+// Resolves every host to the server, for `localhost` not to be tried over IPv6 first
+struct Localhost(SocketAddr);
+
+impl Resolver for Localhost {
+    fn resolve(&self, _host: &str, _port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        let addr = self.0;
+        Box::pin(async move { Ok(vec![addr]) })
+    }
+}
+
+fn options(addr: SocketAddr, url: &str, root_ca: Option<&[u8]>) -> NatsClientOptions {
+    NatsClientOptions {
+        cluster_uris: vec![url.to_string()].into(),
+        resolver: DnsResolver::new(Localhost(addr)),
+        tls_root_ca: root_ca.map(Vec::from),
+        ping_interval: 60,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn handshake_with_root_ca() {
+    let addr = tls_server().await;
+    // Upgraded when required by the url or only by the server INFO
+    for scheme in ["tls", "nats"].iter() {
+        let url = format!("{}://localhost:{}", scheme, addr.port());
+        let client = NatsClient::new(options(addr, &url, Some(CA)))
+            .await
+            .unwrap();
+        assert_eq!(client.state().await, NatsClientState::Connected);
+        client.flush(Duration::from_secs(1)).await.unwrap();
+        client.close().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn reject_unknown_ca() {
+    let addr = tls_server().await;
+    let url = format!("tls://localhost:{}", addr.port());
+    let result = NatsClient::new(options(addr, &url, None)).await;
+    assert!(matches!(result, Err(RatsioError::TlsError(_))));
+}
+
+#[tokio::test]
+async fn reject_hostname_mismatch() {
+    let addr = tls_server().await;
+    for host in ["127.0.0.1", "nats.example.com"].iter() {
+        let url = format!("tls://{}:{}", host, addr.port());
+        let result = NatsClient::new(options(addr, &url, Some(CA))).await;
+        assert!(matches!(result, Err(RatsioError::TlsError(_))), "{}", host);
+    }
+}